@@ -0,0 +1,97 @@
+use crate::constants::{
+    DEFAULT_BAR_NUM, DEFAULT_GRAIN_NUM, DEFAULT_INSTANCE_NUM, DEFAULT_MAX_BUFFER_SECONDS,
+    DEFAULT_RECORD_SECONDS, DEFAULT_SAMPLE_RATE, DEFAULT_VOICE_NUM,
+};
+
+/// Engine dimensions chosen by the frontend. Everything that depends on these
+/// is allocated once in `Sampler::new`, so they can't change while running
+/// (apart from the sample rate, see `Sampler::set_sample_rate`).
+///
+/// ```
+/// use grainiac_core::{Sampler, SamplerConfig};
+///
+/// let config = SamplerConfig::new(48000.0)
+///     .instances(2)
+///     .voices(8)
+///     .max_buffer_seconds(30.0);
+/// let (sampler, draw_data) = Sampler::new(config);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerConfig {
+    pub sample_rate: f32,
+    pub instance_num: usize,
+    pub voice_num: usize,
+    pub grain_num: usize,
+    pub bar_num: usize,
+    pub record_seconds: f32,
+    pub max_buffer_seconds: f32,
+}
+
+impl SamplerConfig {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            ..Self::default()
+        }
+    }
+
+    pub fn instances(mut self, instance_num: usize) -> Self {
+        self.instance_num = instance_num;
+        self
+    }
+
+    /// Polyphony of every instance.
+    pub fn voices(mut self, voice_num: usize) -> Self {
+        self.voice_num = voice_num.max(1);
+        self
+    }
+
+    /// Maximum number of overlapping grains per voice.
+    pub fn grains_per_voice(mut self, grain_num: usize) -> Self {
+        self.grain_num = grain_num.max(1);
+        self
+    }
+
+    /// Resolution of the buffer overview sent with the draw data.
+    pub fn bars(mut self, bar_num: usize) -> Self {
+        self.bar_num = bar_num.max(1);
+        self
+    }
+
+    /// Length of a recording.
+    pub fn record_seconds(mut self, seconds: f32) -> Self {
+        self.record_seconds = seconds.max(0.1);
+        self.max_buffer_seconds = self.max_buffer_seconds.max(self.record_seconds);
+        self
+    }
+
+    /// Longest audio file an instance can hold. This is what each instance
+    /// allocates up front, per channel.
+    pub fn max_buffer_seconds(mut self, seconds: f32) -> Self {
+        self.max_buffer_seconds = seconds.max(0.1);
+        self.record_seconds = self.record_seconds.min(self.max_buffer_seconds);
+        self
+    }
+
+    pub(crate) fn record_buffer_size(&self) -> usize {
+        (self.record_seconds * self.sample_rate) as usize
+    }
+
+    pub(crate) fn max_buffer_size(&self) -> usize {
+        (self.max_buffer_seconds * self.sample_rate) as usize
+    }
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            instance_num: DEFAULT_INSTANCE_NUM,
+            voice_num: DEFAULT_VOICE_NUM,
+            grain_num: DEFAULT_GRAIN_NUM,
+            bar_num: DEFAULT_BAR_NUM,
+            record_seconds: DEFAULT_RECORD_SECONDS,
+            max_buffer_seconds: DEFAULT_MAX_BUFFER_SECONDS,
+        }
+    }
+}
@@ -1,6 +1,28 @@
-pub const VOICE_NUM: usize = 16;
-pub const BAR_NUM: usize = 100;
+pub const DEFAULT_SAMPLE_RATE: f32 = 48000.0;
+pub const DEFAULT_INSTANCE_NUM: usize = 4;
+pub const DEFAULT_VOICE_NUM: usize = 16;
+pub const DEFAULT_BAR_NUM: usize = 100;
 
-pub const GRAIN_NUM: usize = 256;
-pub const BUFFER_SIZE_SECONDS_RECORD: f32 = 10.0;
-pub const BUFFER_SIZE_SECONDS_MAX: f32 = 180.0; // 3 min
+pub const DEFAULT_GRAIN_NUM: usize = 256;
+pub const DEFAULT_RECORD_SECONDS: f32 = 10.0;
+pub const DEFAULT_MAX_BUFFER_SECONDS: f32 = 180.0; // 3 min
+pub const STRETCH_GRAIN_SECONDS: f32 = 0.05;
+pub const STEAL_FADE_SECONDS: f32 = 0.005;
+pub const MPE_BEND_RANGE: f32 = 48.0;
+pub const DEFAULT_BEND_RANGE: f32 = 2.0;
+pub const BEND_SMOOTHING_SECONDS: f32 = 0.01;
+pub const MOD_PITCH_RANGE: f32 = 12.0;
+pub const MOD_OCTAVE_RANGE: f32 = 2.0;
+pub const LFO_NUM: usize = 4;
+pub const MOD_UPDATE_INTERVAL: usize = 32;
+pub const DEFAULT_TEMPO: f32 = 120.0;
+pub const PRE_ROLL_SECONDS: f32 = 0.05;
+pub const DEFAULT_RECORD_THRESHOLD: f32 = 0.1;
+pub const DEFAULT_LIVE_DELAY: f32 = 0.5;
+pub const DEFAULT_CUSTOM_SCALE: u16 = 0b0000_1001_0001;
+pub const DEFAULT_CUTOFF: f32 = 2000.0;
+pub const MAX_DELAY_SECONDS: f32 = 4.0;
+pub const DEFAULT_DELAY_SECONDS: f32 = 0.25;
+pub const DEFAULT_DELAY_FEEDBACK: f32 = 0.4;
+pub const DELAY_SMOOTHING_SECONDS: f32 = 0.05;
+pub const FX_MIX_SMOOTHING_SECONDS: f32 = 0.02;
@@ -0,0 +1,83 @@
+use std::f32::consts::PI;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum FilterMode {
+    #[default]
+    Off,
+    LowPass,
+    BandPass,
+    HighPass,
+}
+
+/// Stereo state-variable filter in the trapezoidal form, which stays stable
+/// when the cutoff jumps, e.g. from one grain to the next.
+#[derive(Default, Clone, Copy)]
+pub struct Svf {
+    mode: FilterMode,
+    k: f32,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    ic1eq: [f32; 2],
+    ic2eq: [f32; 2],
+}
+
+impl Svf {
+    /// `resonance` goes from 0 (a gentle slope) to 1 (close to
+    /// self-oscillation).
+    pub fn new(mode: FilterMode, cutoff: f32, resonance: f32, sample_rate: f32) -> Self {
+        let mut filter = Self {
+            mode,
+            ..Default::default()
+        };
+        filter.set(cutoff, resonance, sample_rate);
+        filter
+    }
+
+    pub fn set_mode(&mut self, mode: FilterMode) {
+        self.mode = mode;
+    }
+
+    /// Updates the coefficients but keeps the state, so the cutoff can be
+    /// moved while audio is running.
+    pub fn set(&mut self, cutoff: f32, resonance: f32, sample_rate: f32) {
+        let cutoff = cutoff.clamp(20.0, sample_rate * 0.49);
+        let g = (PI * cutoff / sample_rate).tan();
+        self.k = 2.0 - 1.95 * resonance.clamp(0.0, 1.0);
+        self.a1 = 1.0 / (1.0 + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.mode == FilterMode::Off
+    }
+
+    pub fn reset(&mut self) {
+        self.ic1eq = [0.0; 2];
+        self.ic2eq = [0.0; 2];
+    }
+
+    pub fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
+        if self.is_off() {
+            return input;
+        }
+
+        (self.tick(0, input.0), self.tick(1, input.1))
+    }
+
+    fn tick(&mut self, channel: usize, v0: f32) -> f32 {
+        let v3 = v0 - self.ic2eq[channel];
+        let v1 = self.a1 * self.ic1eq[channel] + self.a2 * v3;
+        let v2 = self.ic2eq[channel] + self.a2 * self.ic1eq[channel] + self.a3 * v3;
+        self.ic1eq[channel] = 2.0 * v1 - self.ic1eq[channel];
+        self.ic2eq[channel] = 2.0 * v2 - self.ic2eq[channel];
+
+        match self.mode {
+            FilterMode::Off => v0,
+            FilterMode::LowPass => v2,
+            FilterMode::BandPass => v1,
+            FilterMode::HighPass => v0 - self.k * v1 - v2,
+        }
+    }
+}
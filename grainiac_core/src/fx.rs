@@ -0,0 +1,372 @@
+use crate::{
+    constants::{
+        DEFAULT_CUTOFF, DEFAULT_DELAY_FEEDBACK, DEFAULT_DELAY_SECONDS, DELAY_SMOOTHING_SECONDS,
+        FX_MIX_SMOOTHING_SECONDS, MAX_DELAY_SECONDS,
+    },
+    filter::{FilterMode, Svf},
+};
+
+// comb and allpass lengths of the Freeverb tank, in samples at 44.1 kHz
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+const REVERB_DAMPING: f32 = 0.4;
+const REVERB_INPUT_GAIN: f32 = 0.015;
+
+/// The insert effects of an instance, run in the order filter, drive, delay
+/// and reverb. Allocates its delay lines on construction and on sample rate
+/// changes only. Stages that are off cost nothing, the delay and reverb drop
+/// their tail once their mix has faded out, so it doesn't come back later.
+pub struct FxChain {
+    filter: Svf,
+    cutoff: f32,
+    resonance: f32,
+    drive: f32,
+    delay: Delay,
+    reverb: [Reverb; 2],
+    reverb_size: f32,
+    reverb_mix: Ramp,
+    sample_rate: f32,
+}
+
+impl FxChain {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut fx = Self {
+            filter: Svf::new(FilterMode::Off, DEFAULT_CUTOFF, 0.0, sample_rate),
+            cutoff: DEFAULT_CUTOFF,
+            resonance: 0.0,
+            drive: 0.0,
+            delay: Delay::new(sample_rate, DEFAULT_DELAY_SECONDS),
+            reverb: [
+                Reverb::new(sample_rate, 0),
+                Reverb::new(sample_rate, STEREO_SPREAD),
+            ],
+            reverb_size: 0.5,
+            reverb_mix: Ramp::new(0.0, sample_rate),
+            sample_rate,
+        };
+        fx.set_reverb_size(fx.reverb_size);
+        fx
+    }
+
+    /// Reallocates the delay lines and clears them. Allocates, so do not call
+    /// this from the audio thread.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.filter.set(self.cutoff, self.resonance, sample_rate);
+        self.filter.reset();
+        self.delay = Delay {
+            feedback: self.delay.feedback,
+            mix: Ramp::new(self.delay.mix.target, sample_rate),
+            ..Delay::new(sample_rate, self.delay.seconds)
+        };
+        self.reverb_mix = Ramp::new(self.reverb_mix.target, sample_rate);
+        self.reverb = [
+            Reverb::new(sample_rate, 0),
+            Reverb::new(sample_rate, STEREO_SPREAD),
+        ];
+        self.set_reverb_size(self.reverb_size);
+    }
+
+    pub fn set_filter_mode(&mut self, mode: FilterMode) {
+        self.filter.set_mode(mode);
+    }
+
+    pub fn set_filter(&mut self, cutoff: f32, resonance: f32) {
+        self.cutoff = cutoff;
+        self.resonance = resonance;
+        self.filter.set(cutoff, resonance, self.sample_rate);
+    }
+
+    /// From 0 (clean) to 1, drives the signal up to 26 dB into a soft
+    /// clipper. Full scale stays at full scale.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive;
+    }
+
+    /// Sets the delay time in seconds. Changes glide to avoid clicks, which
+    /// bends the pitch of the repeats like a tape delay.
+    pub fn set_delay_time(&mut self, seconds: f32) {
+        self.delay.set_time(seconds);
+    }
+
+    pub fn set_delay_feedback(&mut self, feedback: f32) {
+        self.delay.feedback = feedback;
+    }
+
+    pub fn set_delay_mix(&mut self, mix: f32) {
+        self.delay.mix.set(mix);
+    }
+
+    /// From 0 to 1, sets the decay time of the reverb.
+    pub fn set_reverb_size(&mut self, size: f32) {
+        self.reverb_size = size;
+        for reverb in self.reverb.iter_mut() {
+            reverb.set_feedback(0.7 + 0.28 * size);
+        }
+    }
+
+    pub fn set_reverb_mix(&mut self, mix: f32) {
+        self.reverb_mix.set(mix);
+    }
+
+    pub fn is_neutral(&self) -> bool {
+        self.filter.is_off()
+            && self.drive == 0.0
+            && self.delay.mix.is_off()
+            && self.reverb_mix.is_off()
+    }
+
+    pub fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
+        if self.is_neutral() {
+            return input;
+        }
+
+        let (mut l, mut r) = self.filter.process(input);
+
+        if self.drive > 0.0 {
+            let gain = 1.0 + 19.0 * self.drive;
+            let normalize = 1.0 / gain.tanh();
+            l = (l * gain).tanh() * normalize;
+            r = (r * gain).tanh() * normalize;
+        }
+
+        let (l, r) = if self.delay.mix.is_off() {
+            (l, r)
+        } else {
+            self.delay.process((l, r))
+        };
+
+        if self.reverb_mix.is_off() {
+            return (l, r);
+        }
+
+        let mix = self.reverb_mix.next();
+        let reverb_input = (l + r) * REVERB_INPUT_GAIN;
+        let wet_l = self.reverb[0].process(reverb_input);
+        let wet_r = self.reverb[1].process(reverb_input);
+        if self.reverb_mix.is_off() {
+            self.reverb.iter_mut().for_each(Reverb::clear);
+        }
+
+        (l * (1.0 - mix) + wet_l * mix, r * (1.0 - mix) + wet_r * mix)
+    }
+}
+
+/// Glides a mix towards its target to avoid clicks, and lands on it exactly
+/// so a stage faded out to 0 can be skipped.
+struct Ramp {
+    target: f32,
+    current: f32,
+    smoothing: f32,
+}
+
+impl Ramp {
+    fn new(value: f32, sample_rate: f32) -> Self {
+        Self {
+            target: value,
+            current: value,
+            smoothing: 1.0 - (-1.0 / (sample_rate * FX_MIX_SMOOTHING_SECONDS)).exp(),
+        }
+    }
+
+    fn set(&mut self, value: f32) {
+        self.target = value;
+    }
+
+    fn next(&mut self) -> f32 {
+        self.current += (self.target - self.current) * self.smoothing;
+        if (self.target - self.current).abs() < 1e-4 {
+            self.current = self.target;
+        }
+        self.current
+    }
+
+    fn is_off(&self) -> bool {
+        self.target == 0.0 && self.current == 0.0
+    }
+}
+
+struct Delay {
+    buffer: [Vec<f32>; 2],
+    index: usize,
+    // samples written since the line was cleared, anything older is stale
+    filled: usize,
+    seconds: f32,
+    target: f32,
+    current: f32,
+    smoothing: f32,
+    feedback: f32,
+    mix: Ramp,
+    sample_rate: f32,
+}
+
+impl Delay {
+    fn new(sample_rate: f32, seconds: f32) -> Self {
+        let size = (MAX_DELAY_SECONDS * sample_rate) as usize + 2;
+        let mut delay = Self {
+            buffer: [vec![0.0; size], vec![0.0; size]],
+            index: 0,
+            filled: 0,
+            seconds: 0.0,
+            target: 1.0,
+            current: 1.0,
+            smoothing: 1.0 - (-1.0 / (sample_rate * DELAY_SMOOTHING_SECONDS)).exp(),
+            feedback: DEFAULT_DELAY_FEEDBACK,
+            mix: Ramp::new(0.0, sample_rate),
+            sample_rate,
+        };
+        delay.set_time(seconds);
+        // starts at its time instead of gliding to it
+        delay.current = delay.target;
+        delay
+    }
+
+    fn set_time(&mut self, seconds: f32) {
+        self.seconds = seconds.clamp(0.0, MAX_DELAY_SECONDS);
+        self.target = (self.seconds * self.sample_rate).max(1.0);
+    }
+
+    fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
+        self.current += (self.target - self.current) * self.smoothing;
+        let mix = self.mix.next();
+
+        let size = self.buffer[0].len();
+        let pos = self.index as f32 + size as f32 - self.current;
+        let index = pos as usize;
+        let fraction = pos - index as f32;
+        let a = index % size;
+        let b = (index + 1) % size;
+        let is_valid = self.current.ceil() as usize <= self.filled;
+
+        let mut output = [0.0; 2];
+        for (channel, sample) in [input.0, input.1].into_iter().enumerate() {
+            let buffer = &mut self.buffer[channel];
+            let wet = if is_valid {
+                buffer[a] + (buffer[b] - buffer[a]) * fraction
+            } else {
+                0.0
+            };
+            buffer[self.index] = sample + wet * self.feedback;
+            output[channel] = sample * (1.0 - mix) + wet * mix;
+        }
+
+        self.index = (self.index + 1) % size;
+        self.filled = (self.filled + 1).min(size);
+        if self.mix.is_off() {
+            self.clear();
+        }
+        (output[0], output[1])
+    }
+
+    /// Forgets the tail without touching the line, which is far too long to
+    /// zero within one sample.
+    fn clear(&mut self) {
+        self.filled = 0;
+        self.current = self.target;
+    }
+}
+
+/// One channel of a Freeverb style reverb, eight damped combs into four
+/// allpasses.
+struct Reverb {
+    combs: [Comb; 8],
+    allpasses: [Allpass; 4],
+}
+
+impl Reverb {
+    fn new(sample_rate: f32, spread: usize) -> Self {
+        let scale = |length: usize| ((length + spread) as f32 * sample_rate / 44100.0) as usize;
+        Self {
+            combs: COMB_TUNING.map(|length| Comb::new(scale(length))),
+            allpasses: ALLPASS_TUNING.map(|length| Allpass::new(scale(length))),
+        }
+    }
+
+    fn set_feedback(&mut self, feedback: f32) {
+        for comb in self.combs.iter_mut() {
+            comb.feedback = feedback;
+        }
+    }
+
+    fn clear(&mut self) {
+        for comb in self.combs.iter_mut() {
+            comb.filled = 0;
+            comb.store = 0.0;
+        }
+        for allpass in self.allpasses.iter_mut() {
+            allpass.filled = 0;
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let mut output = self.combs.iter_mut().map(|comb| comb.process(input)).sum();
+        for allpass in self.allpasses.iter_mut() {
+            output = allpass.process(output);
+        }
+        output
+    }
+}
+
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filled: usize,
+    feedback: f32,
+    store: f32,
+}
+
+impl Comb {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+            filled: 0,
+            feedback: 0.0,
+            store: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let size = self.buffer.len();
+        let output = if self.filled < size {
+            0.0
+        } else {
+            self.buffer[self.index]
+        };
+        self.store = output * (1.0 - REVERB_DAMPING) + self.store * REVERB_DAMPING;
+        self.buffer[self.index] = input + self.store * self.feedback;
+        self.index = (self.index + 1) % size;
+        self.filled = (self.filled + 1).min(size);
+        output
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+    filled: usize,
+}
+
+impl Allpass {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+            filled: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let size = self.buffer.len();
+        let delayed = if self.filled < size {
+            0.0
+        } else {
+            self.buffer[self.index]
+        };
+        self.buffer[self.index] = input + delayed * 0.5;
+        self.index = (self.index + 1) % size;
+        self.filled = (self.filled + 1).min(size);
+        delayed - input
+    }
+}
@@ -1,26 +1,29 @@
-use std::f64::consts::PI;
-
-use crate::voice::PlayDirection;
+use crate::{filter::Svf, voice::PlayDirection, window::GrainWindow};
 
 #[derive(Default, Clone, Copy)]
 pub struct GrainData {
     pub pos: f32,
     pub gain: f32,
     pub stereo_pos: f32,
+    /// Slot of the grain in its voice.
+    pub grain: usize,
+    /// How far the grain moves through the buffer per sample.
+    pub speed: f32,
 }
 
 #[derive(Default, Copy, Clone)]
 pub struct Grain {
-    env: Envelope,
     pub active: bool,
     length: usize,
     counter: usize,
+    phase_inc: f32,
     pos: f32,
     pitch: f32,
     buffersize: usize,
     gain: f32,
     stereo_pos: f32,
     grain_direction: PlayDirection,
+    filter: Svf,
 }
 
 impl Grain {
@@ -36,32 +39,50 @@ impl Grain {
         self.active = true;
         self.pos = start_pos;
         self.length = length;
-        self.env.set_inc(1.0 / length as f32);
+        self.phase_inc = 1.0 / length as f32;
         self.pitch = pitch;
         self.buffersize = buffersize;
         self.stereo_pos = stereo_pos;
         self.grain_direction = grain_direction;
+        self.filter = Svf::default();
+    }
+
+    /// Filters the grain until it is activated again, it plays unfiltered
+    /// otherwise.
+    pub fn set_filter(&mut self, filter: Svf) {
+        self.filter = filter;
     }
 
-    pub fn update(&mut self, gain: f32) -> GrainData {
-        match self.grain_direction {
-            PlayDirection::Forward => {
-                self.pos += self.pitch;
+    /// Runs a sample read at the position of the grain through its filter.
+    pub fn filter(&mut self, sample: (f32, f32)) -> (f32, f32) {
+        self.filter.process(sample)
+    }
 
-                if self.pos >= self.buffersize as f32 {
-                    self.pos = 0.0;
-                }
+    /// Advances the grain by one sample. `bend` scales the pitch the grain
+    /// was started with, so running grains follow the pitch bend.
+    pub fn update(&mut self, gain: f32, bend: f32, window: &GrainWindow) -> GrainData {
+        let is_forward = match self.grain_direction {
+            PlayDirection::Forward => true,
+            PlayDirection::Backward => false,
+            // plays the first half forward and then returns to where it started
+            PlayDirection::BackAndForth => self.counter * 2 < self.length,
+        };
+
+        if is_forward {
+            self.pos += self.pitch * bend;
+
+            if self.pos >= self.buffersize as f32 {
+                self.pos = 0.0;
             }
-            PlayDirection::Backward => {
-                self.pos -= self.pitch;
+        } else {
+            self.pos -= self.pitch * bend;
 
-                if self.pos <= 0.0 {
-                    self.pos = self.buffersize as f32;
-                }
+            if self.pos <= 0.0 {
+                self.pos = self.buffersize as f32;
             }
         }
 
-        self.gain = self.env.next_sample() * gain;
+        self.gain = window.read(self.counter as f32 * self.phase_inc) * gain;
 
         self.counter += 1;
 
@@ -73,6 +94,8 @@ impl Grain {
             pos: self.pos,
             gain: self.gain,
             stereo_pos: self.stereo_pos,
+            grain: 0,
+            speed: self.pitch * bend,
         }
     }
 
@@ -80,36 +103,5 @@ impl Grain {
         self.active = false;
         self.counter = 0;
         self.gain = 0.0;
-        self.env.reset();
-    }
-}
-
-#[derive(Default, Copy, Clone)]
-struct Envelope {
-    inc: f64,
-    phase: f64,
-    sin0: f64,
-    sin1: f64,
-    dsin: f64,
-}
-
-impl Envelope {
-    pub fn reset(&mut self) {
-        self.phase = 0.0;
-        self.sin0 = (self.phase * PI).sin();
-        self.sin1 = ((self.phase - self.inc) * PI).sin();
-        self.dsin = 2.0 * (self.inc * PI).cos();
-    }
-
-    pub fn set_inc(&mut self, inc: f32) {
-        self.inc = inc as f64;
-        self.reset();
-    }
-
-    pub fn next_sample(&mut self) -> f32 {
-        let sinx = self.dsin * self.sin0 - self.sin1;
-        self.sin1 = self.sin0;
-        self.sin0 = sinx;
-        sinx as f32
     }
 }
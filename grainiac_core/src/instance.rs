@@ -1,8 +1,22 @@
 use crate::{
-    constants::{BUFFER_SIZE_SECONDS_MAX, BUFFER_SIZE_SECONDS_RECORD, GRAIN_NUM},
+    config::SamplerConfig,
+    constants::{
+        DEFAULT_TEMPO, LFO_NUM, MAX_DELAY_SECONDS, MOD_OCTAVE_RANGE, MOD_PITCH_RANGE,
+        MOD_UPDATE_INTERVAL, PRE_ROLL_SECONDS,
+    },
+    filter::FilterMode,
+    fx::FxChain,
     grain::GrainData,
-    voice::{self, Voice},
-    State, BAR_NUM, VOICE_NUM,
+    interpolation::{Interpolation, Interpolator},
+    modulation::{Lfo, LfoShape, ModTarget, Modulated},
+    resample::resample,
+    scale::Scale,
+    tempo::Division,
+    voice::{
+        self, Expression, GrainScheduling, NoteExpression, VelocityCurve, Voice, VoiceStealing,
+    },
+    window::{GrainWindow, WindowShape},
+    PeakFollower, State,
 };
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -12,103 +26,919 @@ pub enum Mode {
 }
 
 pub struct Instance {
-    pub buffer: Vec<f32>,
+    pub buffer: [Vec<f32>; 2],
     pub buffer_to_draw: BufferToDraw,
     pub rec_buffer_size: usize,
     pub max_buffer_size: usize,
     pub current_buffer_size: usize,
     pub write_index: usize,
+    record_start: usize,
+    record_end: usize,
+    input_peak: PeakFollower,
+    pre_roll: [Vec<f32>; 2],
+    pre_roll_index: usize,
+    pre_roll_len: usize,
+    live_index: usize,
     pub voices: Vec<Voice>,
     pub grain_data: Vec<GrainData>,
     pub state: State,
+    note_count: u64,
+    channel_expressions: [Expression; 16],
+    lfos: [Lfo; LFO_NUM],
+    mod_counter: usize,
+    tempo: f32,
+    config: SamplerConfig,
+    interpolator: Interpolator,
+    window: GrainWindow,
+    stretch_window: GrainWindow,
+    fx: FxChain,
 }
 
 impl Instance {
-    pub fn new(sample_rate: f32) -> Self {
-        let max_buffer_size = (BUFFER_SIZE_SECONDS_MAX * sample_rate) as usize;
-        let rec_buffer_size = (BUFFER_SIZE_SECONDS_RECORD * sample_rate) as usize;
+    pub fn new(config: &SamplerConfig) -> Self {
+        let max_buffer_size = config.max_buffer_size();
+        let rec_buffer_size = config.record_buffer_size();
         let loop_area = (0.25, 0.5);
 
         Self {
-            buffer: vec![0.0; max_buffer_size],
-            buffer_to_draw: BufferToDraw::new(BAR_NUM),
+            buffer: [vec![0.0; max_buffer_size], vec![0.0; max_buffer_size]],
+            buffer_to_draw: BufferToDraw::new(config.bar_num, rec_buffer_size),
             rec_buffer_size,
             max_buffer_size,
             current_buffer_size: rec_buffer_size,
             write_index: 0,
+            record_start: 0,
+            record_end: rec_buffer_size,
+            input_peak: PeakFollower::new(250.0, config.sample_rate),
+            pre_roll: pre_roll_buffer(config.sample_rate),
+            pre_roll_index: 0,
+            pre_roll_len: 0,
+            live_index: 0,
             voices: {
-                let mut voices: Vec<Voice> = Vec::with_capacity(VOICE_NUM);
-                for _ in 0..VOICE_NUM {
-                    voices.push(Voice::new(sample_rate, loop_area.clone()));
+                let mut voices: Vec<Voice> = Vec::with_capacity(config.voice_num);
+                for _ in 0..config.voice_num {
+                    voices.push(Voice::new(config, loop_area));
                 }
                 voices
             },
-            grain_data: Vec::with_capacity(VOICE_NUM * GRAIN_NUM),
+            grain_data: Vec::with_capacity(config.voice_num * config.grain_num),
             state: State::new(),
+            note_count: 0,
+            channel_expressions: [Expression::default(); 16],
+            lfos: [(); LFO_NUM].map(|_| Lfo::new()),
+            mod_counter: 0,
+            tempo: DEFAULT_TEMPO,
+            config: *config,
+            interpolator: Interpolator::new(),
+            window: GrainWindow::new(),
+            stretch_window: {
+                let mut window = GrainWindow::new();
+                window.set_shape(WindowShape::Hann);
+                window
+            },
+            fx: FxChain::new(config.sample_rate),
         }
     }
 
+    /// Reallocates the buffers for a new sample rate and resamples the
+    /// current content. Allocates, so do not call this from the audio thread.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        if sample_rate == self.config.sample_rate {
+            return;
+        }
+
+        let content = [
+            resample(
+                &self.buffer[0][..self.current_buffer_size],
+                self.config.sample_rate,
+                sample_rate,
+            ),
+            resample(
+                &self.buffer[1][..self.current_buffer_size],
+                self.config.sample_rate,
+                sample_rate,
+            ),
+        ];
+
+        self.config.sample_rate = sample_rate;
+        self.max_buffer_size = self.config.max_buffer_size();
+        self.rec_buffer_size = self.config.record_buffer_size();
+        self.buffer = [
+            vec![0.0; self.max_buffer_size],
+            vec![0.0; self.max_buffer_size],
+        ];
+        self.state.is_recording = false;
+        self.state.is_armed = false;
+        self.set_live(false);
+        self.write_index = 0;
+        self.input_peak = PeakFollower::new(250.0, sample_rate);
+        self.pre_roll = pre_roll_buffer(sample_rate);
+        self.pre_roll_index = 0;
+        self.pre_roll_len = 0;
+
+        for voice in self.voices.iter_mut() {
+            voice.set_sample_rate(sample_rate);
+        }
+        self.fx.set_sample_rate(sample_rate);
+
+        self.load_audio(content);
+    }
+
+    /// Starts recording. A plain recording replaces the buffer from the
+    /// start, an overdub keeps layering onto the current buffer until it is
+    /// stopped and punch limits either of them to the current loop region.
     pub fn record(&mut self) {
+        self.start_recording(self.rec_buffer_size);
+    }
+
+    /// Starts recording, or stops a running recording. A plain recording
+    /// keeps going until it is stopped or the buffer is full and the buffer
+    /// then takes the recorded length.
+    pub fn toggle_record(&mut self) {
+        if self.state.is_recording {
+            self.stop_recording();
+        } else {
+            self.start_recording(self.max_buffer_size);
+        }
+    }
+
+    /// Waits for the input to exceed the record threshold and then starts a
+    /// recording like [`Instance::toggle_record`], including a short pre-roll
+    /// so the attack isn't cut off.
+    pub fn arm(&mut self) {
+        if !self.state.is_recording {
+            self.state.is_armed = true;
+            self.pre_roll_len = 0;
+        }
+    }
+
+    pub fn disarm(&mut self) {
+        self.state.is_armed = false;
+    }
+
+    /// Sets the input level from 0 to 1 that starts an armed recording.
+    pub fn set_record_threshold(&mut self, value: f32) {
+        self.state.record_threshold = value.clamp(0.0, 1.0);
+    }
+
+    fn update_armed(&mut self, input: (f32, f32)) {
+        if self.input_peak.value < self.state.record_threshold {
+            let size = self.pre_roll[0].len();
+            self.pre_roll[0][self.pre_roll_index] = input.0;
+            self.pre_roll[1][self.pre_roll_index] = input.1;
+            self.pre_roll_index = (self.pre_roll_index + 1) % size;
+            self.pre_roll_len = (self.pre_roll_len + 1).min(size);
+            return;
+        }
+
+        self.state.is_armed = false;
+        self.start_recording(self.max_buffer_size);
+
+        let size = self.pre_roll[0].len();
+        let start = (self.pre_roll_index + size - self.pre_roll_len) % size;
+        for offset in 0..self.pre_roll_len {
+            if !self.state.is_recording {
+                break;
+            }
+            let index = (start + offset) % size;
+            self.write((self.pre_roll[0][index], self.pre_roll[1][index]));
+        }
+        self.pre_roll_len = 0;
+    }
+
+    fn start_recording(&mut self, length: usize) {
+        self.set_live(false);
+
+        let (start, end) = if self.state.is_punch {
+            let start = self.modulated(ModTarget::LoopStart);
+            let end = (start + self.modulated(ModTarget::LoopLength)).min(1.0);
+            let size = self.current_buffer_size as f32;
+            ((start * size) as usize, (end * size) as usize)
+        } else if self.state.is_overdub {
+            (0, self.current_buffer_size)
+        } else {
+            (0, length)
+        };
+
+        if start >= end {
+            return;
+        }
+
+        if !self.state.is_punch && !self.state.is_overdub {
+            self.buffer_to_draw.resize(length);
+        } else {
+            self.buffer_to_draw.seek(start);
+        }
+
         self.state.is_recording = true;
+        self.write_index = start;
+        self.record_start = start;
+        self.record_end = end;
+    }
+
+    pub fn stop_recording(&mut self) {
+        if !self.state.is_recording {
+            return;
+        }
+
+        if !self.state.is_punch && !self.state.is_overdub && self.write_index > 0 {
+            let sample_num = self.write_index;
+            self.buffer_to_draw.stretch(sample_num);
+            for voice in self.voices.iter_mut() {
+                voice.resize(sample_num);
+            }
+            self.current_buffer_size = sample_num;
+            self.update_target(ModTarget::LoopLength);
+        }
+
+        self.state.is_recording = false;
         self.write_index = 0;
-        self.buffer_to_draw.resize(self.rec_buffer_size);
+        self.buffer_to_draw.reset();
+    }
+
+    /// Records the input continuously into a ring buffer of the record length
+    /// and granulates it a fixed distance behind the write head.
+    pub fn set_live(&mut self, is_live: bool) {
+        if is_live == self.state.is_live {
+            return;
+        }
+
+        self.state.is_live = is_live;
+        if is_live {
+            self.stop_recording();
+            self.state.is_armed = false;
+            self.live_index = 0;
+            self.current_buffer_size = self.rec_buffer_size;
+            self.buffer_to_draw.resize(self.rec_buffer_size);
+            for voice in self.voices.iter_mut() {
+                voice.resize(self.rec_buffer_size);
+            }
+            self.update_target(ModTarget::LoopLength);
+        } else {
+            self.buffer_to_draw.reset();
+            for voice in self.voices.iter_mut() {
+                voice.set_live_pos(None);
+            }
+        }
+    }
+
+    /// Stops writing the live input and keeps granulating what was written.
+    pub fn set_freeze(&mut self, is_frozen: bool) {
+        self.state.is_frozen = is_frozen;
+    }
+
+    /// Sets how far behind the write head grains are read in live mode, in
+    /// seconds.
+    pub fn set_live_delay(&mut self, seconds: f32) {
+        self.state.live_delay = seconds.max(0.0);
+    }
+
+    fn write_live(&mut self, sample: (f32, f32)) {
+        self.buffer[0][self.live_index] = sample.0;
+        self.buffer[1][self.live_index] = sample.1;
+        self.live_index = (self.live_index + 1) % self.current_buffer_size;
+
+        self.buffer_to_draw.update((sample.0 + sample.1) * 0.5);
+        if self.live_index == 0 {
+            self.buffer_to_draw.seek(0);
+        }
+    }
+
+    fn live_pos(&self) -> f32 {
+        let size = self.current_buffer_size as f32;
+        let delay = (self.state.live_delay * self.config.sample_rate).min(size - 1.0);
+        (self.live_index as f32 - delay).rem_euclid(size)
+    }
+
+    /// Mixes new input with the buffer instead of replacing it.
+    pub fn set_overdub(&mut self, is_overdub: bool) {
+        self.state.is_overdub = is_overdub;
+    }
+
+    /// Sets how much of the buffer is kept while overdubbing, from 0 to 1.
+    pub fn set_feedback(&mut self, value: f32) {
+        self.state.feedback = value.clamp(0.0, 1.0);
+    }
+
+    /// Only records from the start to the end of the loop region.
+    pub fn set_punch(&mut self, is_punch: bool) {
+        self.state.is_punch = is_punch;
+    }
+
+    /// Starts a note with a velocity from 0 to 1. Notes outside of the key,
+    /// velocity or channel zone of the instance are ignored.
+    pub fn note_on(&mut self, channel: u8, midi_note: usize, velocity: f32) {
+        if !self.is_in_zone(channel, midi_note, velocity) {
+            return;
+        }
+
+        let index = match self.state.voice_stealing {
+            VoiceStealing::SameNote => self
+                .voices
+                .iter()
+                .position(|voice| voice.midi_note == midi_note && voice.channel == channel)
+                .or_else(|| self.free_voice()),
+            _ => self.free_voice(),
+        };
+
+        let Some(index) = index.or_else(|| self.voice_to_steal(midi_note)) else {
+            return;
+        };
+
+        let velocity = self.state.velocity_curve.apply(velocity);
+
+        self.note_count += 1;
+        let voice = &mut self.voices[index];
+        voice.note_order = self.note_count;
+        voice.channel = channel;
+        voice.set_expressions(self.channel_expressions[channel as usize % 16]);
+        if voice.is_playing {
+            voice.steal(midi_note, velocity);
+        } else {
+            voice.note_on(midi_note, velocity);
+        }
+    }
+
+    pub fn note_off(&mut self, channel: u8, midi_note: usize) {
+        if let Some(voice) = self.voices.iter_mut().find(|voice| {
+            voice.midi_note == midi_note && voice.channel == channel && !voice.is_release()
+        }) {
+            voice.note_off();
+        }
+    }
+
+    /// Applies an expression to the playing notes matching `channel` and
+    /// `midi_note`.
+    pub fn note_expression(&mut self, channel: u8, midi_note: usize, expression: NoteExpression) {
+        for voice in self.voices.iter_mut() {
+            if voice.midi_note == midi_note && voice.channel == channel {
+                voice.set_expression(expression);
+            }
+        }
+    }
+
+    /// Applies an expression to every note on `channel`. It is remembered
+    /// and applied to new notes as well, since MPE controllers send the
+    /// initial expression of a note before the note on.
+    pub fn channel_expression(&mut self, channel: u8, expression: NoteExpression) {
+        self.channel_expressions[channel as usize % 16].apply(expression);
+        for voice in self.voices.iter_mut() {
+            if voice.midi_note != 0 && voice.channel == channel {
+                voice.set_expression(expression);
+            }
+        }
+    }
+
+    pub fn is_in_zone(&self, channel: u8, midi_note: usize, velocity: f32) -> bool {
+        let state = &self.state;
+        let is_key = (state.key_low as usize..=state.key_high as usize).contains(&midi_note);
+        let is_velocity = (state.velocity_low..=state.velocity_high).contains(&velocity);
+
+        is_key && is_velocity && self.is_on_channel(channel)
+    }
+
+    fn is_on_channel(&self, channel: u8) -> bool {
+        self.state.midi_channel.is_none_or(|c| c == channel)
+    }
+
+    /// Bends all notes by `value` from -1 to 1, scaled to the bend range.
+    pub fn pitch_bend(&mut self, channel: u8, value: f32) {
+        if self.is_on_channel(channel) {
+            self.state.pitch_bend = value.clamp(-1.0, 1.0);
+            self.update_target(ModTarget::Pitch);
+        }
+    }
+
+    /// Sets the mod wheel from 0 to 1.
+    pub fn mod_wheel(&mut self, channel: u8, value: f32) {
+        if self.is_on_channel(channel) {
+            self.state.mod_wheel = value.clamp(0.0, 1.0);
+            self.update_target(self.state.mod_wheel_target);
+        }
+    }
+
+    /// Sets the pitch bend range in semitones.
+    pub fn set_bend_range(&mut self, semitones: f32) {
+        self.state.bend_range = semitones.max(0.0);
+        self.update_target(ModTarget::Pitch);
+    }
+
+    pub fn set_mod_wheel_target(&mut self, target: ModTarget) {
+        let previous = self.state.mod_wheel_target;
+        self.state.mod_wheel_target = target;
+        self.update_target(previous);
+        self.update_target(target);
+    }
+
+    /// Sets how far the mod wheel moves its target, from -1 to 1.
+    pub fn set_mod_wheel_amount(&mut self, value: f32) {
+        self.state.mod_wheel_amount = value.clamp(-1.0, 1.0);
+        self.update_target(self.state.mod_wheel_target);
+    }
+
+    /// Sets the tempo in beats per minute for synced LFOs, density, loop
+    /// length, play speed and delay.
+    pub fn set_tempo(&mut self, tempo: f32) {
+        let tempo = tempo.max(1.0);
+        if tempo == self.tempo {
+            return;
+        }
+
+        self.tempo = tempo;
+        self.update_target(ModTarget::Density);
+        self.update_target(ModTarget::LoopLength);
+        self.update_delay_time();
+    }
+
+    /// Triggers one grain per `division` of the tempo, or follows the
+    /// density in Hz with `None`.
+    pub fn set_density_sync(&mut self, sync: Option<Division>) {
+        self.state.density_sync = sync;
+        self.update_target(ModTarget::Density);
+    }
+
+    /// Sets the loop length in beats, or as a fraction of the buffer with
+    /// `None`.
+    pub fn set_loop_beats(&mut self, beats: Option<u8>) {
+        self.state.loop_beats = beats.filter(|beats| *beats > 0);
+        self.update_target(ModTarget::LoopLength);
+    }
+
+    /// Sets the play speed so that the loop is traversed in a whole number of
+    /// bars, or uses the play speed with `None`.
+    pub fn set_speed_bars(&mut self, bars: Option<u8>) {
+        self.state.speed_bars = bars.filter(|bars| *bars > 0);
+        self.update_play_speed();
+    }
+
+    fn update_play_speed(&mut self) {
+        let speed = match self.state.speed_bars {
+            Some(bars) => {
+                let start = self.modulated(ModTarget::LoopStart);
+                let end = (start + self.modulated(ModTarget::LoopLength)).min(1.0);
+                let loop_samples = (end - start) * self.current_buffer_size as f32;
+                let bar_samples = Division::Bar.seconds(self.tempo) * self.config.sample_rate;
+                loop_samples / (bars as f32 * bar_samples)
+            }
+            None => self.state.play_speed,
+        };
+
+        for voice in self.voices.iter_mut() {
+            voice.set_play_speed(speed);
+        }
+    }
+
+    pub fn set_lfo_shape(&mut self, lfo: usize, shape: LfoShape) {
+        if let Some(settings) = self.state.lfos.get_mut(lfo) {
+            settings.shape = shape;
+        }
+    }
+
+    pub fn set_lfo_target(&mut self, lfo: usize, target: ModTarget) {
+        if let Some(settings) = self.state.lfos.get_mut(lfo) {
+            let previous = settings.target;
+            settings.target = target;
+            self.update_target(previous);
+            self.update_target(target);
+        }
+    }
+
+    /// Sets the rate in cycles per second.
+    pub fn set_lfo_rate(&mut self, lfo: usize, rate: f32) {
+        if let Some(settings) = self.state.lfos.get_mut(lfo) {
+            settings.rate = rate.max(0.0);
+        }
+    }
+
+    /// Sets how far the LFO moves its target, from -1 to 1.
+    pub fn set_lfo_depth(&mut self, lfo: usize, depth: f32) {
+        if let Some(settings) = self.state.lfos.get_mut(lfo) {
+            settings.depth = depth.clamp(-1.0, 1.0);
+        }
+    }
+
+    /// Syncs a cycle of the LFO to a division of the tempo, or frees it with
+    /// `None`.
+    pub fn set_lfo_sync(&mut self, lfo: usize, sync: Option<Division>) {
+        if let Some(settings) = self.state.lfos.get_mut(lfo) {
+            settings.sync = sync;
+        }
+    }
+
+    fn update_lfos(&mut self) {
+        let interval = MOD_UPDATE_INTERVAL as f32 / self.config.sample_rate;
+        for (lfo, settings) in self.lfos.iter_mut().zip(self.state.lfos.iter()) {
+            let rate = match settings.sync {
+                Some(division) => 1.0 / division.seconds(self.tempo),
+                None => settings.rate,
+            };
+            lfo.advance(settings.shape, rate * interval);
+        }
+
+        for index in 0..LFO_NUM {
+            self.update_target(self.state.lfos[index].target);
+        }
+    }
+
+    /// The sum of all modulation of `target`.
+    fn modulation(&self, target: ModTarget) -> f32 {
+        if target == ModTarget::Off {
+            return 0.0;
+        }
+
+        let mut modulation = 0.0;
+        if self.state.mod_wheel_target == target {
+            modulation += self.state.mod_wheel * self.state.mod_wheel_amount;
+        }
+        for (lfo, settings) in self.lfos.iter().zip(self.state.lfos.iter()) {
+            if settings.target == target {
+                modulation += lfo.value * settings.depth;
+            }
+        }
+        modulation
+    }
+
+    /// The value of `target` after modulation. The pitch is the offset of the
+    /// global pitch in semitones, including the pitch bend.
+    fn modulated(&self, target: ModTarget) -> f32 {
+        let modulation = self.modulation(target);
+        let state = &self.state;
+        let octaves = 2.0f32.powf(modulation * MOD_OCTAVE_RANGE);
+
+        match target {
+            ModTarget::LoopStart => (state.loop_start + modulation).clamp(0.0, 0.99),
+            ModTarget::LoopLength => {
+                let loop_length = match state.loop_beats {
+                    Some(beats) => {
+                        let seconds = beats as f32 * Division::Quarter.seconds(self.tempo);
+                        seconds * self.config.sample_rate / self.current_buffer_size.max(1) as f32
+                    }
+                    None => state.loop_length,
+                };
+                (loop_length + modulation).clamp(0.0, 1.0)
+            }
+            ModTarget::Density => {
+                let density = match state.density_sync {
+                    Some(division) => 1.0 / division.seconds(self.tempo),
+                    None => state.density,
+                };
+                density * octaves
+            }
+            ModTarget::GrainLength => state.grain_length * octaves,
+            ModTarget::Spray => (state.spray + modulation).clamp(0.0, 1.0),
+            ModTarget::Pan => (state.pan + modulation).clamp(-1.0, 1.0),
+            ModTarget::Spread => (state.spread + modulation).clamp(0.0, 1.0),
+            ModTarget::Pitch => state.pitch_bend * state.bend_range + modulation * MOD_PITCH_RANGE,
+            ModTarget::Gain => (state.gain * (1.0 + modulation)).max(0.0),
+            ModTarget::Off => 0.0,
+        }
+    }
+
+    pub fn modulated_values(&self) -> Modulated {
+        Modulated {
+            loop_start: self.modulated(ModTarget::LoopStart),
+            loop_length: self.modulated(ModTarget::LoopLength),
+            density: self.modulated(ModTarget::Density),
+            grain_length: self.modulated(ModTarget::GrainLength),
+            spray: self.modulated(ModTarget::Spray),
+            pan: self.modulated(ModTarget::Pan),
+            spread: self.modulated(ModTarget::Spread),
+            pitch: self.state.pitch as f32 + self.modulated(ModTarget::Pitch),
+            gain: self.modulated(ModTarget::Gain),
+        }
+    }
+
+    /// Passes the modulated value of `target` on to the voices.
+    fn update_target(&mut self, target: ModTarget) {
+        let value = self.modulated(target);
+
+        for voice in self.voices.iter_mut() {
+            match target {
+                ModTarget::LoopStart => voice.set_loop_start(value),
+                ModTarget::LoopLength => voice.set_loop_length(value),
+                ModTarget::Density => voice.set_density(value),
+                ModTarget::GrainLength => voice.set_grain_length(value),
+                ModTarget::Spray => voice.set_spray(value),
+                ModTarget::Pan => voice.set_pan(value),
+                ModTarget::Spread => voice.set_spread(value),
+                ModTarget::Pitch => voice.set_bend(value),
+                // the gain is modulated while rendering
+                ModTarget::Gain | ModTarget::Off => {}
+            }
+        }
+
+        if matches!(target, ModTarget::LoopStart | ModTarget::LoopLength) {
+            self.update_play_speed();
+        }
+    }
+
+    fn free_voice(&self) -> Option<usize> {
+        self.voices.iter().position(|voice| !voice.is_playing)
+    }
+
+    /// Never picks a voice that is already being stolen, which would drop the
+    /// note it is fading out for.
+    fn voice_to_steal(&self, midi_note: usize) -> Option<usize> {
+        let voices = self
+            .voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| !voice.is_stealing());
+
+        let (index, voice) = match self.state.voice_stealing {
+            VoiceStealing::Oldest | VoiceStealing::SameNote => {
+                voices.min_by_key(|(_, voice)| voice.note_order)?
+            }
+            VoiceStealing::Quietest => voices.min_by(|(_, a), (_, b)| a.gain.total_cmp(&b.gain))?,
+            VoiceStealing::LowestPriority => {
+                let (index, voice) = voices.max_by_key(|(_, voice)| voice.midi_note)?;
+                if voice.midi_note <= midi_note {
+                    return None;
+                }
+                (index, voice)
+            }
+            VoiceStealing::HighestPriority => {
+                let (index, voice) = voices.min_by_key(|(_, voice)| voice.midi_note)?;
+                if voice.midi_note >= midi_note {
+                    return None;
+                }
+                (index, voice)
+            }
+        };
+
+        voice.is_playing.then_some(index)
+    }
+
+    pub fn set_voice_stealing(&mut self, voice_stealing: VoiceStealing) {
+        self.state.voice_stealing = voice_stealing;
+    }
+
+    pub fn set_key_range(&mut self, low: u8, high: u8) {
+        self.state.key_low = low.min(127);
+        self.state.key_high = high.min(127);
+    }
+
+    pub fn set_velocity_range(&mut self, low: f32, high: f32) {
+        self.state.velocity_low = low.clamp(0.0, 1.0);
+        self.state.velocity_high = high.clamp(0.0, 1.0);
+    }
+
+    /// Only listens to notes on `channel`, or to all channels with `None`.
+    pub fn set_midi_channel(&mut self, channel: Option<u8>) {
+        self.state.midi_channel = channel;
+    }
+
+    pub fn set_scheduling(&mut self, scheduling: GrainScheduling) {
+        self.state.scheduling = scheduling;
+        for voice in self.voices.iter_mut() {
+            voice.set_scheduling(scheduling);
+        }
+    }
+
+    pub fn set_jitter(&mut self, value: f32) {
+        self.state.jitter = value;
+        for voice in self.voices.iter_mut() {
+            voice.set_jitter(value);
+        }
+    }
+
+    pub fn set_pitch_spray(&mut self, semitones: f32) {
+        self.state.pitch_spray = semitones.max(0.0);
+        for voice in self.voices.iter_mut() {
+            voice.set_pitch_spray(self.state.pitch_spray);
+        }
+    }
+
+    pub fn set_reverse_probability(&mut self, probability: f32) {
+        self.state.reverse_probability = probability.clamp(0.0, 1.0);
+        for voice in self.voices.iter_mut() {
+            voice.set_reverse_probability(self.state.reverse_probability);
+        }
+    }
+
+    pub fn set_filter_mode(&mut self, mode: FilterMode) {
+        self.state.filter_mode = mode;
+        for voice in self.voices.iter_mut() {
+            voice.set_filter_mode(mode);
+        }
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.state.cutoff = cutoff.clamp(20.0, 20000.0);
+        for voice in self.voices.iter_mut() {
+            voice.set_cutoff(self.state.cutoff);
+        }
+    }
+
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.state.resonance = resonance.clamp(0.0, 1.0);
+        for voice in self.voices.iter_mut() {
+            voice.set_resonance(self.state.resonance);
+        }
+    }
+
+    pub fn set_cutoff_spray(&mut self, octaves: f32) {
+        self.state.cutoff_spray = octaves.max(0.0);
+        for voice in self.voices.iter_mut() {
+            voice.set_cutoff_spray(self.state.cutoff_spray);
+        }
+    }
+
+    pub fn set_resonance_spray(&mut self, amount: f32) {
+        self.state.resonance_spray = amount.clamp(0.0, 1.0);
+        for voice in self.voices.iter_mut() {
+            voice.set_resonance_spray(self.state.resonance_spray);
+        }
+    }
+
+    pub fn set_fx_filter_mode(&mut self, mode: FilterMode) {
+        self.state.fx_filter_mode = mode;
+        self.fx.set_filter_mode(mode);
+    }
+
+    pub fn set_fx_cutoff(&mut self, cutoff: f32) {
+        self.state.fx_cutoff = cutoff.clamp(20.0, 20000.0);
+        self.fx
+            .set_filter(self.state.fx_cutoff, self.state.fx_resonance);
+    }
+
+    pub fn set_fx_resonance(&mut self, resonance: f32) {
+        self.state.fx_resonance = resonance.clamp(0.0, 1.0);
+        self.fx
+            .set_filter(self.state.fx_cutoff, self.state.fx_resonance);
+    }
+
+    pub fn set_drive(&mut self, drive: f32) {
+        self.state.drive = drive.clamp(0.0, 1.0);
+        self.fx.set_drive(self.state.drive);
+    }
+
+    pub fn set_delay_time(&mut self, seconds: f32) {
+        self.state.delay_time = seconds.clamp(0.0, MAX_DELAY_SECONDS);
+        self.update_delay_time();
+    }
+
+    /// Sets the delay time to a division of the tempo, or uses the delay time
+    /// in seconds with `None`. Synced times are limited to the longest delay
+    /// time.
+    pub fn set_delay_sync(&mut self, sync: Option<Division>) {
+        self.state.delay_sync = sync;
+        self.update_delay_time();
+    }
+
+    fn update_delay_time(&mut self) {
+        let seconds = match self.state.delay_sync {
+            Some(division) => division.seconds(self.tempo),
+            None => self.state.delay_time,
+        };
+        self.fx.set_delay_time(seconds);
+    }
+
+    pub fn set_delay_feedback(&mut self, feedback: f32) {
+        self.state.delay_feedback = feedback.clamp(0.0, 0.95);
+        self.fx.set_delay_feedback(self.state.delay_feedback);
+    }
+
+    pub fn set_delay_mix(&mut self, mix: f32) {
+        self.state.delay_mix = mix.clamp(0.0, 1.0);
+        self.fx.set_delay_mix(self.state.delay_mix);
+    }
+
+    pub fn set_reverb_size(&mut self, size: f32) {
+        self.state.reverb_size = size.clamp(0.0, 1.0);
+        self.fx.set_reverb_size(self.state.reverb_size);
+    }
+
+    pub fn set_reverb_mix(&mut self, mix: f32) {
+        self.state.reverb_mix = mix.clamp(0.0, 1.0);
+        self.fx.set_reverb_mix(self.state.reverb_mix);
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.state.scale = scale;
+        self.update_pitch_classes();
+    }
+
+    /// Sets the pitch classes of `Scale::Custom` as bits, the lowest bit
+    /// being the note itself.
+    pub fn set_custom_scale(&mut self, pitch_classes: u16) {
+        self.state.custom_scale = pitch_classes & 0b1111_1111_1111;
+        self.update_pitch_classes();
+    }
+
+    fn update_pitch_classes(&mut self) {
+        let pitch_classes = self.state.scale.pitch_classes(self.state.custom_scale);
+        for voice in self.voices.iter_mut() {
+            voice.set_pitch_classes(pitch_classes);
+        }
+    }
+
+    pub fn set_velocity_curve(&mut self, velocity_curve: VelocityCurve) {
+        self.state.velocity_curve = velocity_curve;
+    }
+
+    pub fn set_velocity_to_density(&mut self, value: f32) {
+        self.state.velocity_to_density = value;
+        for voice in self.voices.iter_mut() {
+            voice.set_velocity_to_density(value);
+        }
+    }
+
+    pub fn set_velocity_to_spray(&mut self, value: f32) {
+        self.state.velocity_to_spray = value;
+        for voice in self.voices.iter_mut() {
+            voice.set_velocity_to_spray(value);
+        }
+    }
+
+    pub fn set_velocity_to_attack(&mut self, value: f32) {
+        self.state.velocity_to_attack = value;
+        for voice in self.voices.iter_mut() {
+            voice.set_velocity_to_attack(value);
+        }
     }
 
     pub fn set_mode(&mut self, mode: Mode) {
         self.state.mode = mode;
     }
 
-    pub fn set_play_speed(&mut self, value: f32) {
-        self.state.play_speed = value;
+    pub fn set_stretch(&mut self, is_stretch: bool) {
+        self.state.is_stretch = is_stretch;
         for voice in self.voices.iter_mut() {
-            voice.set_play_speed(value);
+            voice.set_stretch(is_stretch);
         }
     }
 
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.state.interpolation = interpolation;
+    }
+
+    pub fn set_window(&mut self, shape: WindowShape) {
+        self.state.window = shape;
+        self.window.set_shape(shape);
+    }
+
+    pub fn set_window_flatness(&mut self, value: f32) {
+        self.state.window_flatness = value;
+        self.window.set_flatness(value);
+    }
+
+    pub fn set_window_skew(&mut self, value: f32) {
+        self.state.window_skew = value;
+        self.window.set_skew(value);
+    }
+
+    pub fn set_play_speed(&mut self, value: f32) {
+        self.state.play_speed = value;
+        self.update_play_speed();
+    }
+
     pub fn set_loop_start(&mut self, value: f32) {
         self.state.loop_start = value.clamp(0.0, 0.99);
-        for voice in self.voices.iter_mut() {
-            voice.set_loop_start(value.clamp(0.0, 0.99));
-        }
+        self.update_target(ModTarget::LoopStart);
     }
 
     pub fn set_loop_length(&mut self, value: f32) {
         self.state.loop_length = value;
-        for voice in self.voices.iter_mut() {
-            voice.set_loop_length(value);
-        }
+        self.update_target(ModTarget::LoopLength);
     }
 
     pub fn set_density(&mut self, value: f32) {
         self.state.density = value;
-        for voice in self.voices.iter_mut() {
-            voice.set_density(value);
-        }
+        self.update_target(ModTarget::Density);
     }
 
     pub fn set_spray(&mut self, value: f32) {
         self.state.spray = value;
-        for voice in self.voices.iter_mut() {
-            voice.set_spray(value);
-        }
+        self.update_target(ModTarget::Spray);
     }
 
     pub fn set_grain_length(&mut self, value: f32) {
         self.state.grain_length = value;
-        for voice in self.voices.iter_mut() {
-            voice.set_grain_length(value);
-        }
+        self.update_target(ModTarget::GrainLength);
     }
 
     pub fn set_gain(&mut self, value: f32) {
         self.state.gain = value;
     }
 
+    pub fn set_spread(&mut self, value: f32) {
+        self.state.spread = value;
+        self.update_target(ModTarget::Spread);
+    }
+
+    pub fn set_pan(&mut self, value: f32) {
+        self.state.pan = value;
+        self.update_target(ModTarget::Pan);
+    }
+
+    /// Voices fading out for a pending note are left alone, so the pending
+    /// note still starts.
     pub fn toggle_hold(&mut self) {
         match self.state.is_hold {
             true => {
                 for voice in self.voices.iter_mut() {
-                    if voice.midi_note != 0 {
+                    if voice.midi_note != 0 && !voice.is_stealing() {
                         voice.env.set_state(voice::EnvelopeState::Release);
                     }
                 }
@@ -116,7 +946,7 @@ impl Instance {
             }
             false => {
                 for voice in self.voices.iter_mut() {
-                    if voice.midi_note != 0 {
+                    if voice.midi_note != 0 && !voice.is_stealing() {
                         voice.env.set_state(voice::EnvelopeState::Hold);
                     }
                 }
@@ -125,83 +955,140 @@ impl Instance {
         }
     }
 
-    fn write(&mut self, sample: f32) {
-        self.buffer[self.write_index] = sample;
-        self.write_index = self.write_index + 1;
+    fn write(&mut self, mut sample: (f32, f32)) {
+        if self.state.is_overdub {
+            sample.0 += self.buffer[0][self.write_index] * self.state.feedback;
+            sample.1 += self.buffer[1][self.write_index] * self.state.feedback;
+        }
 
-        self.buffer_to_draw.update(sample);
+        self.buffer[0][self.write_index] = sample.0;
+        self.buffer[1][self.write_index] = sample.1;
+        self.write_index += 1;
 
-        if self.write_index >= self.rec_buffer_size {
-            self.write_index = 0;
-            self.state.is_recording = false;
-            self.buffer_to_draw.reset();
+        self.buffer_to_draw.update((sample.0 + sample.1) * 0.5);
+
+        if self.write_index < self.record_end {
+            return;
+        }
+
+        if self.state.is_overdub {
+            self.write_index = self.record_start;
+            self.buffer_to_draw.seek(self.record_start);
+        } else {
+            self.stop_recording();
         }
     }
 
-    pub fn load_audio(&mut self, samples: Vec<f32>) {
-        let sample_num = samples.len().min(self.max_buffer_size);
-        let samples = &samples[..sample_num];
+    /// Loads a stereo buffer. Both channels are expected to have the same length.
+    pub fn load_audio(&mut self, samples: [Vec<f32>; 2]) {
+        self.set_live(false);
+
+        let sample_num = samples[0]
+            .len()
+            .min(samples[1].len())
+            .min(self.max_buffer_size);
 
         for voice in self.voices.iter_mut() {
             voice.resize(sample_num);
-            voice.set_play_speed(self.state.play_speed);
         }
 
         self.current_buffer_size = sample_num;
+        self.update_target(ModTarget::LoopLength);
 
         self.buffer_to_draw.resize(sample_num);
-        for sample in samples.iter() {
-            self.buffer_to_draw.update(*sample);
+        for (l, r) in samples[0].iter().zip(samples[1].iter()).take(sample_num) {
+            self.buffer_to_draw.update((l + r) * 0.5);
         }
 
-        self.buffer[..sample_num].copy_from_slice(samples);
+        for (buffer, channel) in self.buffer.iter_mut().zip(samples.iter()) {
+            buffer[..sample_num].copy_from_slice(&channel[..sample_num]);
+        }
     }
 
-    pub fn render(&mut self, input_sample: &f32) -> (f32, f32) {
+    pub fn render(&mut self, input: (f32, f32)) -> (f32, f32) {
+        if self.mod_counter == 0 {
+            self.update_lfos();
+        }
+        self.mod_counter = (self.mod_counter + 1) % MOD_UPDATE_INTERVAL;
+
+        self.input_peak.process(input.0.abs().max(input.1.abs()));
+        if self.state.is_armed {
+            self.update_armed(input);
+        }
+
         if self.state.is_recording {
-            self.write(*input_sample);
+            self.write(input);
+        } else if self.state.is_live && !self.state.is_frozen {
+            self.write_live(input);
         }
+        let live_pos = self.state.is_live.then(|| self.live_pos());
 
         let mut output = (0.0, 0.0);
+        let buffer_l = &self.buffer[0][..self.current_buffer_size];
+        let buffer_r = &self.buffer[1][..self.current_buffer_size];
+        let interpolation = self.state.interpolation;
+
+        let is_stretching = self.state.mode == Mode::Tape && self.state.is_stretch;
+        let window = if is_stretching {
+            &self.stretch_window
+        } else {
+            &self.window
+        };
 
         self.grain_data.clear();
         for voice in self.voices.iter_mut() {
             if voice.midi_note != 0 {
-                self.grain_data.extend(voice.render(self.state.mode));
+                if live_pos.is_some() {
+                    voice.set_live_pos(live_pos);
+                }
+                let first_grain = self.grain_data.len();
+                voice.render(self.state.mode, window, &mut self.grain_data);
 
-                if self.state.mode == Mode::Tape {
-                    let play_index_int = voice.play_pos.floor() as usize;
-                    let next_index = (play_index_int + 1) % self.current_buffer_size;
-                    let frac = voice.play_pos - play_index_int as f32;
+                // grains are read right away, as their filters live in the voice
+                for grain_data in self.grain_data[first_grain..].iter() {
+                    let (pos, speed) = (grain_data.pos, grain_data.speed);
+                    let next_l = self.interpolator.read(interpolation, buffer_l, pos, speed);
+                    let next_r = self.interpolator.read(interpolation, buffer_r, pos, speed);
 
-                    let next_sample = self.buffer[play_index_int] * (1.0 - frac as f32)
-                        + self.buffer[next_index] * frac as f32;
+                    let sample = voice.filter_grain(grain_data.grain, (next_l, next_r));
+                    let (l, r) = pan_stereo(sample, grain_data.stereo_pos);
 
-                    output.0 += next_sample * voice.gain;
-                    output.1 += next_sample * voice.gain;
+                    output.0 += 0.5 * l * grain_data.gain;
+                    output.1 += 0.5 * r * grain_data.gain;
                 }
-            }
-        }
 
-        for grain_data in self.grain_data.iter() {
-            let play_index_int = grain_data.pos as usize;
-            let next_index = (play_index_int + 1) % self.current_buffer_size;
-            let frac = grain_data.pos - play_index_int as f32;
+                if self.state.mode == Mode::Tape && !is_stretching {
+                    let (pos, speed) = (voice.play_pos, voice.tape_speed());
+                    let next_l = self.interpolator.read(interpolation, buffer_l, pos, speed);
+                    let next_r = self.interpolator.read(interpolation, buffer_r, pos, speed);
 
-            let left_gain = 0.5 * (1.0 - grain_data.stereo_pos);
-            let right_gain = 0.5 * (1.0 + grain_data.stereo_pos);
+                    output.0 += next_l * voice.gain;
+                    output.1 += next_r * voice.gain;
+                }
+            }
+        }
 
-            let next_sample =
-                self.buffer[play_index_int] * (1.0 - frac) + self.buffer[next_index] * frac;
+        let gain = self.modulated(ModTarget::Gain);
+        output.0 *= 0.5 * gain;
+        output.1 *= 0.5 * gain;
 
-            output.0 += next_sample * grain_data.gain * left_gain;
-            output.1 += next_sample * grain_data.gain * right_gain;
-        }
+        self.fx.process(output)
+    }
+}
 
-        output.0 *= 0.5 * self.state.gain;
-        output.1 *= 0.5 * self.state.gain;
+fn pre_roll_buffer(sample_rate: f32) -> [Vec<f32>; 2] {
+    let size = ((PRE_ROLL_SECONDS * sample_rate) as usize).max(1);
+    [vec![0.0; size], vec![0.0; size]]
+}
 
-        output
+/// Pans a stereo sample by moving the opposite channel over instead of
+/// dropping it, so the original image is kept at the centre and a mono
+/// source behaves like a plain linear pan.
+fn pan_stereo(sample: (f32, f32), pos: f32) -> (f32, f32) {
+    if pos >= 0.0 {
+        (sample.0 * (1.0 - pos), sample.1 + sample.0 * pos)
+    } else {
+        (sample.0 - sample.1 * pos, sample.1 * (1.0 + pos))
     }
 }
 
@@ -214,10 +1101,10 @@ pub struct BufferToDraw {
 }
 
 impl BufferToDraw {
-    fn new(original_size: usize) -> Self {
+    fn new(bar_num: usize, original_size: usize) -> Self {
         Self {
-            buffer: vec![0.0; BAR_NUM],
-            samples_per_bar: (original_size as f32 / BAR_NUM as f32) as usize,
+            buffer: vec![0.0; bar_num],
+            samples_per_bar: (original_size as f32 / bar_num as f32) as usize,
             sample_counter: 0,
             current_bar: 0,
             sample_sum: 0.0,
@@ -235,7 +1122,7 @@ impl BufferToDraw {
         self.sample_sum += sample * sample;
         self.sample_counter += 1;
 
-        if self.sample_counter > self.samples_per_bar && self.current_bar < BAR_NUM {
+        if self.sample_counter > self.samples_per_bar && self.current_bar < self.buffer.len() {
             let mean_square = self.sample_sum / self.samples_per_bar as f32;
             self.buffer[self.current_bar] = mean_square.sqrt();
 
@@ -252,9 +1139,35 @@ impl BufferToDraw {
         self.current_bar = 0;
     }
 
+    /// Continues drawing at `index` of the buffer, keeping what was drawn.
+    pub fn seek(&mut self, index: usize) {
+        self.sample_sum = 0.0;
+        self.sample_counter = 0;
+        self.current_bar = index / self.samples_per_bar.max(1);
+    }
+
+    /// Spreads what was drawn so far over the whole overview, for a buffer
+    /// that ended up `vec_size` samples long.
+    pub fn stretch(&mut self, vec_size: usize) {
+        if self.sample_counter > 0 && self.current_bar < self.buffer.len() {
+            let mean_square = self.sample_sum / self.sample_counter as f32;
+            self.buffer[self.current_bar] = mean_square.sqrt();
+            self.current_bar += 1;
+        }
+
+        let drawn = self.current_bar.clamp(1, self.buffer.len());
+        let bar_num = self.buffer.len();
+        for index in (0..bar_num).rev() {
+            self.buffer[index] = self.buffer[index * drawn / bar_num];
+        }
+
+        self.reset();
+        self.samples_per_bar = (vec_size as f32 / bar_num as f32) as usize;
+    }
+
     pub fn resize(&mut self, vec_size: usize) {
         self.reset();
         self.buffer.fill(0.0);
-        self.samples_per_bar = (vec_size as f32 / BAR_NUM as f32) as usize;
+        self.samples_per_bar = (vec_size as f32 / self.buffer.len() as f32) as usize;
     }
 }
@@ -0,0 +1,149 @@
+use std::f32::consts::PI;
+
+const SINC_TAPS: usize = 8;
+const SINC_PHASES: usize = 256;
+// widening the kernel costs a tap per sample of speed, so it stops here
+const MAX_SINC_SPEED: f32 = 4.0;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Interpolation {
+    Linear,
+    Hermite,
+    Sinc,
+}
+
+/// Reads fractional positions from a looping buffer. The windowed sinc kernel
+/// is tabulated on construction so that reading never allocates.
+pub struct Interpolator {
+    sinc_table: Vec<[f32; SINC_TAPS]>,
+    /// One half of the same kernel, to be stretched when reading faster than
+    /// one sample per sample.
+    kernel: Vec<f32>,
+}
+
+impl Interpolator {
+    pub fn new() -> Self {
+        let mut sinc_table = vec![[0.0; SINC_TAPS]; SINC_PHASES + 1];
+
+        for (phase, row) in sinc_table.iter_mut().enumerate() {
+            let frac = phase as f32 / SINC_PHASES as f32;
+
+            for (tap, coeff) in row.iter_mut().enumerate() {
+                let x = (tap as f32 - (SINC_TAPS / 2 - 1) as f32) - frac;
+                *coeff = sinc(x) * blackman(x);
+            }
+
+            let sum: f32 = row.iter().sum();
+            for coeff in row.iter_mut() {
+                *coeff /= sum;
+            }
+        }
+
+        let kernel = (0..=SINC_TAPS / 2 * SINC_PHASES)
+            .map(|index| {
+                let x = index as f32 / SINC_PHASES as f32;
+                sinc(x) * blackman(x)
+            })
+            .collect();
+
+        Self { sinc_table, kernel }
+    }
+
+    /// `speed` is how far `pos` moves per sample. Above 1 the sinc kernel
+    /// lowers its cutoff to match, so pitching up doesn't alias.
+    pub fn read(&self, interpolation: Interpolation, buffer: &[f32], pos: f32, speed: f32) -> f32 {
+        let len = buffer.len() as isize;
+        if len == 0 {
+            return 0.0;
+        }
+
+        let index = pos.floor() as isize;
+        let frac = pos - pos.floor();
+        let sample = |offset: isize| buffer[(index + offset).rem_euclid(len) as usize];
+
+        match interpolation {
+            Interpolation::Linear => sample(0) * (1.0 - frac) + sample(1) * frac,
+            Interpolation::Hermite => {
+                let (xm1, x0, x1, x2) = (sample(-1), sample(0), sample(1), sample(2));
+
+                let c1 = 0.5 * (x1 - xm1);
+                let c2 = xm1 - 2.5 * x0 + 2.0 * x1 - 0.5 * x2;
+                let c3 = 0.5 * (x2 - xm1) + 1.5 * (x0 - x1);
+
+                ((c3 * frac + c2) * frac + c1) * frac + x0
+            }
+            Interpolation::Sinc if speed.abs() > 1.0 => {
+                let speed = speed.abs().min(MAX_SINC_SPEED);
+                let half_width = SINC_TAPS as f32 / 2.0 * speed;
+                let first = (pos - half_width).floor() as isize + 1;
+                let last = (pos + half_width).ceil() as isize - 1;
+
+                let mut output = 0.0;
+                let mut weight_sum = 0.0;
+                for index in first..=last {
+                    let weight = self.read_kernel((index as f32 - pos).abs() / speed);
+                    output += buffer[index.rem_euclid(len) as usize] * weight;
+                    weight_sum += weight;
+                }
+
+                if weight_sum > 0.0 {
+                    output / weight_sum
+                } else {
+                    0.0
+                }
+            }
+            Interpolation::Sinc => {
+                let phase = frac * SINC_PHASES as f32;
+                let phase_int = (phase as usize).min(SINC_PHASES - 1);
+                let phase_frac = phase - phase_int as f32;
+
+                let row_a = &self.sinc_table[phase_int];
+                let row_b = &self.sinc_table[phase_int + 1];
+
+                let mut output = 0.0;
+                for tap in 0..SINC_TAPS {
+                    let coeff = row_a[tap] + (row_b[tap] - row_a[tap]) * phase_frac;
+                    output += sample(tap as isize - (SINC_TAPS / 2 - 1) as isize) * coeff;
+                }
+                output
+            }
+        }
+    }
+}
+
+impl Interpolator {
+    fn read_kernel(&self, x: f32) -> f32 {
+        let pos = x * SINC_PHASES as f32;
+        let index = pos as usize;
+        if index + 1 >= self.kernel.len() {
+            return 0.0;
+        }
+
+        let frac = pos - index as f32;
+        self.kernel[index] + (self.kernel[index + 1] - self.kernel[index]) * frac
+    }
+}
+
+impl Default for Interpolator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < f32::EPSILON {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+fn blackman(x: f32) -> f32 {
+    let half_width = SINC_TAPS as f32 / 2.0;
+    if x.abs() >= half_width {
+        return 0.0;
+    }
+
+    let phase = PI * x / half_width;
+    0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
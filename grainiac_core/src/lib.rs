@@ -1,17 +1,38 @@
+use constants::{
+    DEFAULT_BEND_RANGE, DEFAULT_CUSTOM_SCALE, DEFAULT_CUTOFF, DEFAULT_DELAY_FEEDBACK,
+    DEFAULT_DELAY_SECONDS, DEFAULT_LIVE_DELAY, DEFAULT_RECORD_THRESHOLD,
+};
 use rtsan_standalone::nonblocking;
 pub use triple_buffer::{triple_buffer, Input, Output};
 use voice::PlayDirection;
 
-use crate::constants::BUFFER_SIZE_SECONDS_RECORD;
 pub use crate::{
-    constants::{BAR_NUM, GRAIN_NUM, VOICE_NUM},
+    config::SamplerConfig,
+    constants::{LFO_NUM, MPE_BEND_RANGE},
+    filter::FilterMode,
     instance::{Instance, Mode},
+    interpolation::Interpolation,
+    modulation::{LfoSettings, LfoShape, ModTarget, Modulated},
+    resample::resample,
+    scale::{pitch_classes_from_intervals, Scale},
+    tempo::Division,
+    voice::{GrainScheduling, NoteExpression, VelocityCurve, VoiceStealing},
+    window::WindowShape,
 };
 
+pub mod config;
 mod constants;
+pub mod filter;
+pub mod fx;
 mod grain;
 pub mod instance;
+pub mod interpolation;
+pub mod modulation;
+mod resample;
+pub mod scale;
+pub mod tempo;
 pub mod voice;
+pub mod window;
 
 #[allow(dead_code)]
 #[derive(Clone)]
@@ -30,9 +51,62 @@ pub struct State {
     pub gain: f32,
     pub is_recording: bool,
     pub is_hold: bool,
+    pub is_stretch: bool,
+    pub is_overdub: bool,
+    pub feedback: f32,
+    pub is_punch: bool,
+    pub is_armed: bool,
+    pub record_threshold: f32,
+    pub is_live: bool,
+    pub is_frozen: bool,
+    pub live_delay: f32,
+    pub pitch_spray: f32,
+    pub scale: Scale,
+    pub custom_scale: u16,
+    pub reverse_probability: f32,
+    pub filter_mode: FilterMode,
+    pub cutoff: f32,
+    pub resonance: f32,
+    pub cutoff_spray: f32,
+    pub resonance_spray: f32,
+    pub fx_filter_mode: FilterMode,
+    pub fx_cutoff: f32,
+    pub fx_resonance: f32,
+    pub drive: f32,
+    pub delay_time: f32,
+    pub delay_sync: Option<Division>,
+    pub delay_feedback: f32,
+    pub delay_mix: f32,
+    pub reverb_size: f32,
+    pub reverb_mix: f32,
     pub play_dir: PlayDirection,
     pub grain_dir: PlayDirection,
     pub mode: Mode,
+    pub interpolation: Interpolation,
+    pub window: WindowShape,
+    pub window_flatness: f32,
+    pub window_skew: f32,
+    pub voice_stealing: VoiceStealing,
+    pub velocity_curve: VelocityCurve,
+    pub velocity_to_density: f32,
+    pub velocity_to_spray: f32,
+    pub velocity_to_attack: f32,
+    pub key_low: u8,
+    pub key_high: u8,
+    pub velocity_low: f32,
+    pub velocity_high: f32,
+    pub midi_channel: Option<u8>,
+    pub bend_range: f32,
+    pub pitch_bend: f32,
+    pub mod_wheel: f32,
+    pub mod_wheel_target: ModTarget,
+    pub mod_wheel_amount: f32,
+    pub lfos: [LfoSettings; LFO_NUM],
+    pub scheduling: GrainScheduling,
+    pub jitter: f32,
+    pub density_sync: Option<Division>,
+    pub loop_beats: Option<u8>,
+    pub speed_bars: Option<u8>,
 }
 
 impl State {
@@ -52,9 +126,62 @@ impl State {
             gain: 0.5,
             is_recording: false,
             is_hold: false,
+            is_stretch: false,
+            is_overdub: false,
+            feedback: 1.0,
+            is_punch: false,
+            is_armed: false,
+            record_threshold: DEFAULT_RECORD_THRESHOLD,
+            is_live: false,
+            is_frozen: false,
+            live_delay: DEFAULT_LIVE_DELAY,
+            pitch_spray: 0.0,
+            scale: Scale::Free,
+            custom_scale: DEFAULT_CUSTOM_SCALE,
+            reverse_probability: 0.0,
+            filter_mode: FilterMode::Off,
+            cutoff: DEFAULT_CUTOFF,
+            resonance: 0.0,
+            cutoff_spray: 0.0,
+            resonance_spray: 0.0,
+            fx_filter_mode: FilterMode::Off,
+            fx_cutoff: DEFAULT_CUTOFF,
+            fx_resonance: 0.0,
+            drive: 0.0,
+            delay_time: DEFAULT_DELAY_SECONDS,
+            delay_sync: None,
+            delay_feedback: DEFAULT_DELAY_FEEDBACK,
+            delay_mix: 0.0,
+            reverb_size: 0.5,
+            reverb_mix: 0.0,
             play_dir: PlayDirection::Forward,
             grain_dir: PlayDirection::Forward,
             mode: Mode::Grain,
+            interpolation: Interpolation::Linear,
+            window: WindowShape::Sine,
+            window_flatness: 0.0,
+            window_skew: 0.0,
+            voice_stealing: VoiceStealing::Oldest,
+            velocity_curve: VelocityCurve::Linear,
+            velocity_to_density: 0.0,
+            velocity_to_spray: 0.0,
+            velocity_to_attack: 0.0,
+            key_low: 0,
+            key_high: 127,
+            velocity_low: 0.0,
+            velocity_high: 1.0,
+            midi_channel: None,
+            bend_range: DEFAULT_BEND_RANGE,
+            pitch_bend: 0.0,
+            mod_wheel: 0.0,
+            mod_wheel_target: ModTarget::Off,
+            mod_wheel_amount: 1.0,
+            lfos: [LfoSettings::default(); LFO_NUM],
+            scheduling: GrainScheduling::Synchronous,
+            jitter: 0.5,
+            density_sync: None,
+            loop_beats: None,
+            speed_bars: None,
         }
     }
 }
@@ -65,23 +192,31 @@ pub struct DrawData {
     pub play_heads: Vec<Option<f32>>,
     pub buffer: Vec<f32>,
     pub state: State,
+    pub modulated: Modulated,
     pub input_peak: f32,
     pub output_peak: f32,
 }
 
 impl DrawData {
-    pub fn new() -> Self {
+    pub fn new(config: &SamplerConfig) -> Self {
         Self {
-            grain_data: vec![None; VOICE_NUM * GRAIN_NUM],
-            play_heads: vec![None; VOICE_NUM],
-            buffer: vec![0.0; BAR_NUM],
+            grain_data: vec![None; config.voice_num * config.grain_num],
+            play_heads: vec![None; config.voice_num],
+            buffer: vec![0.0; config.bar_num],
             state: State::new(),
+            modulated: Modulated::default(),
             input_peak: 0.0,
             output_peak: 0.0,
         }
     }
 }
 
+impl Default for DrawData {
+    fn default() -> Self {
+        Self::new(&SamplerConfig::default())
+    }
+}
+
 pub struct PeakFollower {
     pub value: f32,
     release_coeff: f32,
@@ -117,7 +252,8 @@ pub struct Sampler {
     instances: Vec<Instance>,
     pub draw_data: Input<Vec<DrawData>>,
     draw_data_update_count: usize,
-    sample_rate: f32,
+    draw_data_interval: usize,
+    config: SamplerConfig,
     input_peak: PeakFollower,
     output_peak: PeakFollower,
     l_select: bool,
@@ -125,20 +261,23 @@ pub struct Sampler {
 }
 
 impl Sampler {
-    pub fn new(sample_rate: f32, instance_num: usize) -> (Self, Output<Vec<DrawData>>) {
-        let (buf_input, buf_output) = triple_buffer(&vec![DrawData::new(); instance_num]);
+    pub fn new(config: SamplerConfig) -> (Self, Output<Vec<DrawData>>) {
+        let sample_rate = config.sample_rate;
+        let (buf_input, buf_output) =
+            triple_buffer(&vec![DrawData::new(&config); config.instance_num]);
         (
             Self {
                 instances: {
-                    let mut instances: Vec<Instance> = Vec::with_capacity(instance_num);
-                    for _ in 0..instance_num {
-                        instances.push(Instance::new(sample_rate))
+                    let mut instances: Vec<Instance> = Vec::with_capacity(config.instance_num);
+                    for _ in 0..config.instance_num {
+                        instances.push(Instance::new(&config))
                     }
                     instances
                 },
                 draw_data: buf_input,
                 draw_data_update_count: 0,
-                sample_rate,
+                draw_data_interval: sample_rate as usize / 33,
+                config,
                 input_peak: PeakFollower::new(250.0, sample_rate),
                 output_peak: PeakFollower::new(250.0, sample_rate),
                 l_select: false,
@@ -148,15 +287,193 @@ impl Sampler {
         )
     }
 
+    /// Reconfigures the engine for the host's sample rate, resampling any
+    /// audio that is already loaded. Allocates, call it on initialization.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        if sample_rate == self.config.sample_rate {
+            return;
+        }
+
+        for instance in self.instances.iter_mut() {
+            instance.set_sample_rate(sample_rate);
+        }
+
+        self.config.sample_rate = sample_rate;
+        self.draw_data_interval = sample_rate as usize / 33;
+        self.input_peak = PeakFollower::new(250.0, sample_rate);
+        self.output_peak = PeakFollower::new(250.0, sample_rate);
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.config.sample_rate
+    }
+
+    pub fn config(&self) -> &SamplerConfig {
+        &self.config
+    }
+
     pub fn record(&mut self, instance_index: usize) {
         if let Some(instance) = self.instances.get_mut(instance_index) {
             instance.record();
         }
     }
 
-    fn get_draw_data(&mut self) {
-        self.draw_data_update_count += 1;
-        if self.draw_data_update_count >= self.sample_rate as usize / 33 {
+    /// Starts a recording that runs until it is stopped, or stops it.
+    pub fn toggle_record(&mut self, instance_index: usize) {
+        if let Some(instance) = self.instances.get_mut(instance_index) {
+            instance.toggle_record();
+        }
+    }
+
+    pub fn stop_recording(&mut self, instance_index: usize) {
+        if let Some(instance) = self.instances.get_mut(instance_index) {
+            instance.stop_recording();
+        }
+    }
+
+    /// Starts a recording once the input exceeds the record threshold.
+    pub fn arm(&mut self, instance_index: usize) {
+        if let Some(instance) = self.instances.get_mut(instance_index) {
+            instance.arm();
+        }
+    }
+
+    pub fn toggle_arm(&mut self, instance_index: usize) {
+        if let Some(instance) = self.instances.get_mut(instance_index) {
+            if instance.state.is_armed {
+                instance.disarm();
+            } else {
+                instance.arm();
+            }
+        }
+    }
+
+    pub fn set_record_threshold(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_record_threshold(value);
+        }
+    }
+
+    pub fn set_live(&mut self, index: usize, value: bool) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_live(value);
+        }
+    }
+
+    pub fn toggle_live(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_live(!instance.state.is_live);
+        }
+    }
+
+    pub fn set_freeze(&mut self, index: usize, value: bool) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_freeze(value);
+        }
+    }
+
+    pub fn toggle_freeze(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_freeze(!instance.state.is_frozen);
+        }
+    }
+
+    pub fn set_live_delay(&mut self, index: usize, seconds: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_live_delay(seconds);
+        }
+    }
+
+    /// Sets how far each grain is randomly transposed, in semitones.
+    pub fn set_pitch_spray(&mut self, index: usize, semitones: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_pitch_spray(semitones);
+        }
+    }
+
+    pub fn set_scale(&mut self, index: usize, scale: Scale) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_scale(scale);
+        }
+    }
+
+    pub fn toggle_scale(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            let scale = match instance.state.scale {
+                Scale::Free => Scale::Chromatic,
+                Scale::Chromatic => Scale::Major,
+                Scale::Major => Scale::Minor,
+                Scale::Minor => Scale::Pentatonic,
+                Scale::Pentatonic => Scale::WholeTone,
+                Scale::WholeTone => Scale::Fifths,
+                Scale::Fifths => Scale::Custom,
+                Scale::Custom => Scale::Free,
+            };
+            instance.set_scale(scale);
+        }
+    }
+
+    pub fn set_scale_from_preset(&mut self, index: usize, value: u8) {
+        let scale = match value {
+            0 => Scale::Free,
+            1 => Scale::Chromatic,
+            2 => Scale::Major,
+            3 => Scale::Minor,
+            4 => Scale::Pentatonic,
+            5 => Scale::WholeTone,
+            6 => Scale::Fifths,
+            _ => Scale::Custom,
+        };
+        self.set_scale(index, scale);
+    }
+
+    /// Sets the intervals in semitones above the note that `Scale::Custom`
+    /// quantizes to.
+    pub fn set_custom_scale(&mut self, index: usize, intervals: &[u8]) {
+        self.set_custom_scale_from_preset(index, pitch_classes_from_intervals(intervals));
+    }
+
+    /// Sets the custom scale from pitch class bits, the lowest bit being the
+    /// note itself.
+    pub fn set_custom_scale_from_preset(&mut self, index: usize, pitch_classes: u16) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_custom_scale(pitch_classes);
+        }
+    }
+
+    pub fn set_overdub(&mut self, index: usize, value: bool) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_overdub(value);
+        }
+    }
+
+    pub fn toggle_overdub(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_overdub(!instance.state.is_overdub);
+        }
+    }
+
+    pub fn set_feedback(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_feedback(value);
+        }
+    }
+
+    pub fn set_punch(&mut self, index: usize, value: bool) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_punch(value);
+        }
+    }
+
+    pub fn toggle_punch(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_punch(!instance.state.is_punch);
+        }
+    }
+
+    fn get_draw_data(&mut self, sample_num: usize) {
+        self.draw_data_update_count += sample_num;
+        if self.draw_data_update_count >= self.draw_data_interval {
             let draw_data = self.draw_data.input_buffer_mut();
             for (i, instance) in self.instances.iter().enumerate() {
                 draw_data[i].grain_data.fill(None);
@@ -185,6 +502,7 @@ impl Sampler {
                 }
 
                 draw_data[i].state = instance.state.clone();
+                draw_data[i].modulated = instance.modulated_values();
                 draw_data[i].input_peak = self.input_peak.value;
                 draw_data[i].output_peak = self.output_peak.value;
             }
@@ -194,25 +512,28 @@ impl Sampler {
         }
     }
 
-    pub fn get_bufs(&mut self) -> Vec<&[f32]> {
+    pub fn get_bufs(&mut self) -> Vec<[&[f32]; 2]> {
         let mut comb = vec![];
         for instance in self.instances.iter() {
-            comb.push(&instance.buffer[0..instance.current_buffer_size]);
+            comb.push([
+                &instance.buffer[0][0..instance.current_buffer_size],
+                &instance.buffer[1][0..instance.current_buffer_size],
+            ]);
         }
 
         comb
     }
 
-    pub fn load_bufs(&mut self, bufs: Vec<f32>) {
-        let chunk_size = (BUFFER_SIZE_SECONDS_RECORD * self.sample_rate) as usize;
-        let chunks = bufs.chunks(chunk_size);
+    pub fn load_bufs(&mut self, bufs: [Vec<f32>; 2]) {
+        let chunk_size = self.config.record_buffer_size();
+        let chunks = bufs[0].chunks(chunk_size).zip(bufs[1].chunks(chunk_size));
 
-        for (instance, chunk) in self.instances.iter_mut().zip(chunks) {
-            instance.load_audio(chunk.to_vec());
+        for (instance, (chunk_l, chunk_r)) in self.instances.iter_mut().zip(chunks) {
+            instance.load_audio([chunk_l.to_vec(), chunk_r.to_vec()]);
         }
     }
 
-    pub fn load_buf(&mut self, buf: Vec<f32>, index: usize) {
+    pub fn load_buf(&mut self, buf: [Vec<f32>; 2], index: usize) {
         if let Some(instance) = self.instances.get_mut(index) {
             instance.load_audio(buf);
         }
@@ -220,33 +541,56 @@ impl Sampler {
 
     #[nonblocking]
     pub fn render(&mut self, stereo_slice: (&mut f32, &mut f32)) {
-        let mut output_l = 0.0;
-        let mut output_r = 0.0;
+        let (output_l, output_r) = self.render_frame(*stereo_slice.0, *stereo_slice.1);
 
-        if !self.r_select {
-            *stereo_slice.1 = 0.0;
-        }
+        self.get_draw_data(1);
+
+        *stereo_slice.0 = output_l;
+        *stereo_slice.1 = output_r;
+    }
 
-        if !self.l_select {
-            *stereo_slice.0 = 0.0;
+    /// Renders a whole block at once. Draw data is published once per block
+    /// instead of being checked for every sample.
+    #[nonblocking]
+    pub fn process_block(&mut self, inputs: (&[f32], &[f32]), outputs: (&mut [f32], &mut [f32])) {
+        let sample_num = outputs.0.len().min(outputs.1.len());
+
+        let frames = inputs
+            .0
+            .iter()
+            .zip(inputs.1.iter())
+            .zip(outputs.0.iter_mut().zip(outputs.1.iter_mut()));
+
+        for ((input_l, input_r), (output_l, output_r)) in frames {
+            (*output_l, *output_r) = self.render_frame(*input_l, *input_r);
         }
 
-        let mono = *stereo_slice.0 + *stereo_slice.1;
+        self.get_draw_data(sample_num);
+    }
+
+    fn render_frame(&mut self, input_l: f32, input_r: f32) -> (f32, f32) {
+        let mut output_l = 0.0;
+        let mut output_r = 0.0;
+
+        // a single selected side is recorded as a mono source on both channels
+        let input = match (self.l_select, self.r_select) {
+            (true, true) => (input_l, input_r),
+            (true, false) => (input_l, input_l),
+            (false, true) => (input_r, input_r),
+            (false, false) => (0.0, 0.0),
+        };
 
-        self.input_peak.process(mono);
+        self.input_peak.process(input.0.abs().max(input.1.abs()));
 
         for instance in self.instances.iter_mut() {
-            let (l, r) = instance.render(&mono);
+            let (l, r) = instance.render(input);
             output_l += l;
             output_r += r;
         }
 
         self.output_peak.process(output_l + output_r);
 
-        self.get_draw_data();
-
-        *stereo_slice.0 = output_l;
-        *stereo_slice.1 = output_r;
+        (output_l, output_r)
     }
 
     pub fn set_select_r(&mut self, select: bool) {
@@ -257,32 +601,355 @@ impl Sampler {
         self.l_select = select
     }
 
-    pub fn note_on(&mut self, midi_note: usize) {
+    /// Starts a note on every instance that isn't on hold. The velocity goes
+    /// from 0 to 1.
+    pub fn note_on(&mut self, channel: u8, midi_note: usize, velocity: f32) {
         for instance in self.instances.iter_mut() {
             if !instance.state.is_hold {
-                for voice in instance.voices.iter_mut() {
-                    if !voice.is_playing {
-                        voice.note_on(midi_note);
-                        break;
-                    }
-                }
+                instance.note_on(channel, midi_note, velocity);
             }
         }
     }
 
-    pub fn note_off(&mut self, midi_note: usize) {
+    pub fn note_off(&mut self, channel: u8, midi_note: usize) {
         for instance in self.instances.iter_mut() {
             if !instance.state.is_hold {
-                for voice in instance.voices.iter_mut() {
-                    if voice.midi_note == midi_note && !voice.is_release() {
-                        voice.note_off();
-                        break;
-                    }
+                instance.note_off(channel, midi_note);
+            }
+        }
+    }
+
+    /// Per-note expression, e.g. CLAP note expressions.
+    pub fn note_expression(&mut self, channel: u8, midi_note: usize, expression: NoteExpression) {
+        for instance in self.instances.iter_mut() {
+            instance.note_expression(channel, midi_note, expression);
+        }
+    }
+
+    /// Per-channel expression for MPE member channels. Pitch bend is
+    /// expected as a `Tuning` scaled to `MPE_BEND_RANGE`.
+    pub fn channel_expression(&mut self, channel: u8, expression: NoteExpression) {
+        for instance in self.instances.iter_mut() {
+            instance.channel_expression(channel, expression);
+        }
+    }
+
+    /// Pitch bend from -1 to 1, e.g. from the master channel. Every instance
+    /// listening on `channel` scales it by its own bend range.
+    pub fn pitch_bend(&mut self, channel: u8, value: f32) {
+        for instance in self.instances.iter_mut() {
+            instance.pitch_bend(channel, value);
+        }
+    }
+
+    /// Mod wheel from 0 to 1, routed to the mod wheel target of every
+    /// instance listening on `channel`.
+    pub fn mod_wheel(&mut self, channel: u8, value: f32) {
+        for instance in self.instances.iter_mut() {
+            instance.mod_wheel(channel, value);
+        }
+    }
+
+    pub fn set_bend_range(&mut self, index: usize, semitones: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_bend_range(semitones);
+        }
+    }
+
+    pub fn set_mod_wheel_target(&mut self, index: usize, target: ModTarget) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_mod_wheel_target(target);
+        }
+    }
+
+    pub fn toggle_mod_wheel_target(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_mod_wheel_target(next_mod_target(instance.state.mod_wheel_target));
+        }
+    }
+
+    pub fn set_mod_wheel_target_from_preset(&mut self, index: usize, value: u8) {
+        self.set_mod_wheel_target(index, mod_target_from_preset(value));
+    }
+
+    pub fn set_mod_wheel_amount(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_mod_wheel_amount(value);
+        }
+    }
+
+    /// Sets the tempo in beats per minute that synced LFOs, density, loop
+    /// length and play speed follow.
+    pub fn set_tempo(&mut self, tempo: f32) {
+        for instance in self.instances.iter_mut() {
+            instance.set_tempo(tempo);
+        }
+    }
+
+    pub fn set_density_sync(&mut self, index: usize, sync: Option<Division>) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_density_sync(sync);
+        }
+    }
+
+    pub fn toggle_density_sync(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_density_sync(next_division(instance.state.density_sync));
+        }
+    }
+
+    /// 0 uses the density in Hz, 1 to 8 trigger a grain every four bars
+    /// down to every thirty-second note.
+    pub fn set_density_sync_from_preset(&mut self, index: usize, value: u8) {
+        self.set_density_sync(index, division_from_preset(value));
+    }
+
+    pub fn set_loop_beats(&mut self, index: usize, beats: Option<u8>) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_loop_beats(beats);
+        }
+    }
+
+    /// 0 uses the loop length as a fraction of the buffer.
+    pub fn set_loop_beats_from_preset(&mut self, index: usize, value: u8) {
+        self.set_loop_beats(index, (value > 0).then_some(value));
+    }
+
+    pub fn set_speed_bars(&mut self, index: usize, bars: Option<u8>) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_speed_bars(bars);
+        }
+    }
+
+    /// 0 uses the play speed.
+    pub fn set_speed_bars_from_preset(&mut self, index: usize, value: u8) {
+        self.set_speed_bars(index, (value > 0).then_some(value));
+    }
+
+    pub fn set_lfo_shape(&mut self, index: usize, lfo: usize, shape: LfoShape) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_lfo_shape(lfo, shape);
+        }
+    }
+
+    pub fn toggle_lfo_shape(&mut self, index: usize, lfo: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            if let Some(settings) = instance.state.lfos.get(lfo) {
+                let shape = match settings.shape {
+                    LfoShape::Sine => LfoShape::Triangle,
+                    LfoShape::Triangle => LfoShape::Saw,
+                    LfoShape::Saw => LfoShape::Square,
+                    LfoShape::Square => LfoShape::SampleAndHold,
+                    LfoShape::SampleAndHold => LfoShape::SmoothRandom,
+                    LfoShape::SmoothRandom => LfoShape::Sine,
+                };
+                instance.set_lfo_shape(lfo, shape);
+            }
+        }
+    }
+
+    pub fn set_lfo_shape_from_preset(&mut self, index: usize, lfo: usize, value: u8) {
+        let shape = match value {
+            0 => LfoShape::Sine,
+            1 => LfoShape::Triangle,
+            2 => LfoShape::Saw,
+            3 => LfoShape::Square,
+            4 => LfoShape::SampleAndHold,
+            _ => LfoShape::SmoothRandom,
+        };
+        self.set_lfo_shape(index, lfo, shape);
+    }
+
+    pub fn set_lfo_target(&mut self, index: usize, lfo: usize, target: ModTarget) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_lfo_target(lfo, target);
+        }
+    }
+
+    pub fn toggle_lfo_target(&mut self, index: usize, lfo: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            if let Some(settings) = instance.state.lfos.get(lfo) {
+                instance.set_lfo_target(lfo, next_mod_target(settings.target));
+            }
+        }
+    }
+
+    pub fn set_lfo_target_from_preset(&mut self, index: usize, lfo: usize, value: u8) {
+        self.set_lfo_target(index, lfo, mod_target_from_preset(value));
+    }
+
+    pub fn set_lfo_rate(&mut self, index: usize, lfo: usize, rate: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_lfo_rate(lfo, rate);
+        }
+    }
+
+    pub fn set_lfo_depth(&mut self, index: usize, lfo: usize, depth: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_lfo_depth(lfo, depth);
+        }
+    }
+
+    pub fn set_lfo_sync(&mut self, index: usize, lfo: usize, sync: Option<Division>) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_lfo_sync(lfo, sync);
+        }
+    }
+
+    pub fn toggle_lfo_sync(&mut self, index: usize, lfo: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            if let Some(settings) = instance.state.lfos.get(lfo) {
+                instance.set_lfo_sync(lfo, next_division(settings.sync));
+            }
+        }
+    }
+
+    /// 0 runs the LFO freely, 1 to 8 sync it from four bars down to a
+    /// thirty-second note.
+    pub fn set_lfo_sync_from_preset(&mut self, index: usize, lfo: usize, value: u8) {
+        self.set_lfo_sync(index, lfo, division_from_preset(value));
+    }
+
+    pub fn set_voice_stealing(&mut self, index: usize, voice_stealing: VoiceStealing) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_voice_stealing(voice_stealing);
+        }
+    }
+
+    pub fn toggle_voice_stealing(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            match instance.state.voice_stealing {
+                VoiceStealing::Oldest => instance.set_voice_stealing(VoiceStealing::Quietest),
+                VoiceStealing::Quietest => instance.set_voice_stealing(VoiceStealing::SameNote),
+                VoiceStealing::SameNote => {
+                    instance.set_voice_stealing(VoiceStealing::LowestPriority)
+                }
+                VoiceStealing::LowestPriority => {
+                    instance.set_voice_stealing(VoiceStealing::HighestPriority)
+                }
+                VoiceStealing::HighestPriority => {
+                    instance.set_voice_stealing(VoiceStealing::Oldest)
+                }
+            }
+        }
+    }
+
+    pub fn set_key_range(&mut self, index: usize, low: u8, high: u8) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_key_range(low, high);
+        }
+    }
+
+    pub fn set_velocity_range(&mut self, index: usize, low: f32, high: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_velocity_range(low, high);
+        }
+    }
+
+    pub fn set_midi_channel(&mut self, index: usize, channel: Option<u8>) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_midi_channel(channel);
+        }
+    }
+
+    /// 0 listens to all channels, 1 to 16 to a single channel.
+    pub fn set_midi_channel_from_preset(&mut self, index: usize, value: u8) {
+        let channel = match value {
+            0 => None,
+            channel => Some(channel.min(16) - 1),
+        };
+        self.set_midi_channel(index, channel);
+    }
+
+    pub fn set_scheduling(&mut self, index: usize, scheduling: GrainScheduling) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_scheduling(scheduling);
+        }
+    }
+
+    pub fn toggle_scheduling(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            match instance.state.scheduling {
+                GrainScheduling::Synchronous => {
+                    instance.set_scheduling(GrainScheduling::Asynchronous)
                 }
+                GrainScheduling::Asynchronous => instance.set_scheduling(GrainScheduling::Jittered),
+                GrainScheduling::Jittered => instance.set_scheduling(GrainScheduling::Synchronous),
+            }
+        }
+    }
+
+    pub fn set_scheduling_from_preset(&mut self, index: usize, value: u8) {
+        let scheduling = match value {
+            0 => GrainScheduling::Synchronous,
+            1 => GrainScheduling::Asynchronous,
+            _ => GrainScheduling::Jittered,
+        };
+        self.set_scheduling(index, scheduling);
+    }
+
+    pub fn set_jitter(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_jitter(value);
+        }
+    }
+
+    pub fn set_velocity_curve(&mut self, index: usize, velocity_curve: VelocityCurve) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_velocity_curve(velocity_curve);
+        }
+    }
+
+    pub fn toggle_velocity_curve(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            match instance.state.velocity_curve {
+                VelocityCurve::Linear => instance.set_velocity_curve(VelocityCurve::Soft),
+                VelocityCurve::Soft => instance.set_velocity_curve(VelocityCurve::Hard),
+                VelocityCurve::Hard => instance.set_velocity_curve(VelocityCurve::Fixed),
+                VelocityCurve::Fixed => instance.set_velocity_curve(VelocityCurve::Linear),
             }
         }
     }
 
+    pub fn set_velocity_curve_from_preset(&mut self, index: usize, value: u8) {
+        let velocity_curve = match value {
+            0 => VelocityCurve::Linear,
+            1 => VelocityCurve::Soft,
+            2 => VelocityCurve::Hard,
+            _ => VelocityCurve::Fixed,
+        };
+        self.set_velocity_curve(index, velocity_curve);
+    }
+
+    pub fn set_velocity_to_density(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_velocity_to_density(value);
+        }
+    }
+
+    pub fn set_velocity_to_spray(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_velocity_to_spray(value);
+        }
+    }
+
+    pub fn set_velocity_to_attack(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_velocity_to_attack(value);
+        }
+    }
+
+    pub fn set_voice_stealing_from_preset(&mut self, index: usize, value: u8) {
+        let voice_stealing = match value {
+            0 => VoiceStealing::Oldest,
+            1 => VoiceStealing::Quietest,
+            2 => VoiceStealing::SameNote,
+            3 => VoiceStealing::LowestPriority,
+            _ => VoiceStealing::HighestPriority,
+        };
+        self.set_voice_stealing(index, voice_stealing);
+    }
+
     pub fn toggle_mode(&mut self, index: usize) {
         if let Some(instance) = self.instances.get_mut(index) {
             match instance.state.mode {
@@ -292,32 +959,270 @@ impl Sampler {
         }
     }
 
-    pub fn set_play_dir_from_preset(&mut self, index: usize, value: u8) {
+    pub fn set_mode(&mut self, index: usize, mode: Mode) {
         if let Some(instance) = self.instances.get_mut(index) {
-            if value == 0 {
-                instance.state.play_dir = PlayDirection::Forward;
-            } else {
-                instance.state.play_dir = PlayDirection::Backward;
+            instance.set_mode(mode);
+        }
+    }
+
+    pub fn set_mode_from_preset(&mut self, index: usize, value: u8) {
+        let mode = if value == 0 { Mode::Grain } else { Mode::Tape };
+        self.set_mode(index, mode);
+    }
+
+    pub fn set_stretch(&mut self, index: usize, value: bool) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_stretch(value);
+        }
+    }
+
+    pub fn toggle_stretch(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_stretch(!instance.state.is_stretch);
+        }
+    }
+
+    pub fn set_interpolation(&mut self, index: usize, interpolation: Interpolation) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_interpolation(interpolation);
+        }
+    }
+
+    pub fn toggle_interpolation(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            match instance.state.interpolation {
+                Interpolation::Linear => instance.set_interpolation(Interpolation::Hermite),
+                Interpolation::Hermite => instance.set_interpolation(Interpolation::Sinc),
+                Interpolation::Sinc => instance.set_interpolation(Interpolation::Linear),
+            }
+        }
+    }
+
+    pub fn set_interpolation_from_preset(&mut self, index: usize, value: u8) {
+        let interpolation = match value {
+            0 => Interpolation::Linear,
+            1 => Interpolation::Hermite,
+            _ => Interpolation::Sinc,
+        };
+        self.set_interpolation(index, interpolation);
+    }
+
+    pub fn set_window(&mut self, index: usize, shape: WindowShape) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_window(shape);
+        }
+    }
+
+    pub fn toggle_window(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            match instance.state.window {
+                WindowShape::Sine => instance.set_window(WindowShape::Hann),
+                WindowShape::Hann => instance.set_window(WindowShape::Gaussian),
+                WindowShape::Gaussian => instance.set_window(WindowShape::Tukey),
+                WindowShape::Tukey => instance.set_window(WindowShape::Trapezoid),
+                WindowShape::Trapezoid => instance.set_window(WindowShape::Exponential),
+                WindowShape::Exponential => instance.set_window(WindowShape::ReverseExponential),
+                WindowShape::ReverseExponential => instance.set_window(WindowShape::Sine),
             }
+        }
+    }
+
+    pub fn set_window_from_preset(&mut self, index: usize, value: u8) {
+        let shape = match value {
+            0 => WindowShape::Sine,
+            1 => WindowShape::Hann,
+            2 => WindowShape::Gaussian,
+            3 => WindowShape::Tukey,
+            4 => WindowShape::Trapezoid,
+            5 => WindowShape::Exponential,
+            _ => WindowShape::ReverseExponential,
+        };
+        self.set_window(index, shape);
+    }
+
+    pub fn set_window_flatness(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_window_flatness(value);
+        }
+    }
+
+    pub fn set_window_skew(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_window_skew(value);
+        }
+    }
+
+    pub fn set_play_dir_from_preset(&mut self, index: usize, value: u8) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.state.play_dir = match value {
+                0 => PlayDirection::Forward,
+                1 => PlayDirection::Backward,
+                _ => PlayDirection::BackAndForth,
+            };
             for voice in instance.voices.iter_mut() {
-                voice.set_play_direction(instance.state.play_dir.clone());
+                voice.set_play_direction(instance.state.play_dir);
             }
         }
     }
 
     pub fn set_grain_dir_from_preset(&mut self, index: usize, value: u8) {
         if let Some(instance) = self.instances.get_mut(index) {
-            if value == 0 {
-                instance.state.grain_dir = PlayDirection::Forward;
-            } else {
-                instance.state.grain_dir = PlayDirection::Backward;
-            }
+            instance.state.grain_dir = match value {
+                0 => PlayDirection::Forward,
+                1 => PlayDirection::Backward,
+                _ => PlayDirection::BackAndForth,
+            };
             for voice in instance.voices.iter_mut() {
-                voice.set_grain_direction(instance.state.grain_dir.clone());
+                voice.set_grain_direction(instance.state.grain_dir);
             }
         }
     }
 
+    /// Sets the chance from 0 to 1 that a grain plays against the grain
+    /// direction.
+    pub fn set_reverse_probability(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_reverse_probability(value);
+        }
+    }
+
+    pub fn set_filter_mode(&mut self, index: usize, mode: FilterMode) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_filter_mode(mode);
+        }
+    }
+
+    pub fn toggle_filter_mode(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            let mode = match instance.state.filter_mode {
+                FilterMode::Off => FilterMode::LowPass,
+                FilterMode::LowPass => FilterMode::BandPass,
+                FilterMode::BandPass => FilterMode::HighPass,
+                FilterMode::HighPass => FilterMode::Off,
+            };
+            instance.set_filter_mode(mode);
+        }
+    }
+
+    pub fn set_filter_mode_from_preset(&mut self, index: usize, value: u8) {
+        self.set_filter_mode(index, filter_mode_from_preset(value));
+    }
+
+    /// Sets the centre cutoff of the grain filter in Hz.
+    pub fn set_cutoff(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_cutoff(value);
+        }
+    }
+
+    pub fn set_resonance(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_resonance(value);
+        }
+    }
+
+    /// Sets how far the cutoff of each grain is randomly moved, in octaves.
+    pub fn set_cutoff_spray(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_cutoff_spray(value);
+        }
+    }
+
+    /// Sets how far the resonance of each grain is randomly moved, from 0 to 1.
+    pub fn set_resonance_spray(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_resonance_spray(value);
+        }
+    }
+
+    pub fn set_fx_filter_mode(&mut self, index: usize, mode: FilterMode) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_fx_filter_mode(mode);
+        }
+    }
+
+    pub fn toggle_fx_filter_mode(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            let mode = match instance.state.fx_filter_mode {
+                FilterMode::Off => FilterMode::LowPass,
+                FilterMode::LowPass => FilterMode::BandPass,
+                FilterMode::BandPass => FilterMode::HighPass,
+                FilterMode::HighPass => FilterMode::Off,
+            };
+            instance.set_fx_filter_mode(mode);
+        }
+    }
+
+    pub fn set_fx_filter_mode_from_preset(&mut self, index: usize, value: u8) {
+        self.set_fx_filter_mode(index, filter_mode_from_preset(value));
+    }
+
+    /// Sets the cutoff of the insert filter in Hz.
+    pub fn set_fx_cutoff(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_fx_cutoff(value);
+        }
+    }
+
+    pub fn set_fx_resonance(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_fx_resonance(value);
+        }
+    }
+
+    pub fn set_drive(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_drive(value);
+        }
+    }
+
+    /// Sets the delay time in seconds, used while the delay is not synced.
+    pub fn set_delay_time(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_delay_time(value);
+        }
+    }
+
+    pub fn set_delay_sync(&mut self, index: usize, sync: Option<Division>) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_delay_sync(sync);
+        }
+    }
+
+    pub fn toggle_delay_sync(&mut self, index: usize) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_delay_sync(next_division(instance.state.delay_sync));
+        }
+    }
+
+    pub fn set_delay_sync_from_preset(&mut self, index: usize, value: u8) {
+        self.set_delay_sync(index, division_from_preset(value));
+    }
+
+    pub fn set_delay_feedback(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_delay_feedback(value);
+        }
+    }
+
+    pub fn set_delay_mix(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_delay_mix(value);
+        }
+    }
+
+    pub fn set_reverb_size(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_reverb_size(value);
+        }
+    }
+
+    pub fn set_reverb_mix(&mut self, index: usize, value: f32) {
+        if let Some(instance) = self.instances.get_mut(index) {
+            instance.set_reverb_mix(value);
+        }
+    }
+
     pub fn set_hold(&mut self, index: usize, value: bool) {
         if let Some(instance) = self.instances.get_mut(index) {
             if instance.state.is_hold != value {
@@ -328,24 +1233,26 @@ impl Sampler {
 
     pub fn toggle_play_dir(&mut self, index: usize) {
         if let Some(instance) = self.instances.get_mut(index) {
-            match instance.state.play_dir {
-                PlayDirection::Forward => instance.state.play_dir = PlayDirection::Backward,
-                PlayDirection::Backward => instance.state.play_dir = PlayDirection::Forward,
-            }
+            instance.state.play_dir = match instance.state.play_dir {
+                PlayDirection::Forward => PlayDirection::Backward,
+                PlayDirection::Backward => PlayDirection::BackAndForth,
+                PlayDirection::BackAndForth => PlayDirection::Forward,
+            };
             for voice in instance.voices.iter_mut() {
-                voice.set_play_direction(instance.state.play_dir.clone());
+                voice.set_play_direction(instance.state.play_dir);
             }
         }
     }
 
     pub fn toggle_grain_dir(&mut self, index: usize) {
         if let Some(instance) = self.instances.get_mut(index) {
-            match instance.state.grain_dir {
-                PlayDirection::Forward => instance.state.grain_dir = PlayDirection::Backward,
-                PlayDirection::Backward => instance.state.grain_dir = PlayDirection::Forward,
-            }
+            instance.state.grain_dir = match instance.state.grain_dir {
+                PlayDirection::Forward => PlayDirection::Backward,
+                PlayDirection::Backward => PlayDirection::BackAndForth,
+                PlayDirection::BackAndForth => PlayDirection::Forward,
+            };
             for voice in instance.voices.iter_mut() {
-                voice.set_grain_direction(instance.state.grain_dir.clone());
+                voice.set_grain_direction(instance.state.grain_dir);
             }
         }
     }
@@ -427,19 +1334,81 @@ impl Sampler {
 
     pub fn set_spread(&mut self, index: usize, value: f32) {
         if let Some(instance) = self.instances.get_mut(index) {
-            instance.state.spread = value;
-            for voice in instance.voices.iter_mut() {
-                voice.set_spread(value);
-            }
+            instance.set_spread(value);
         }
     }
 
     pub fn set_pan(&mut self, index: usize, value: f32) {
         if let Some(instance) = self.instances.get_mut(index) {
-            instance.state.pan = value;
-            for voice in instance.voices.iter_mut() {
-                voice.set_pan(value);
-            }
+            instance.set_pan(value);
         }
     }
 }
+
+fn next_mod_target(target: ModTarget) -> ModTarget {
+    match target {
+        ModTarget::Off => ModTarget::LoopStart,
+        ModTarget::LoopStart => ModTarget::LoopLength,
+        ModTarget::LoopLength => ModTarget::Density,
+        ModTarget::Density => ModTarget::GrainLength,
+        ModTarget::GrainLength => ModTarget::Spray,
+        ModTarget::Spray => ModTarget::Pan,
+        ModTarget::Pan => ModTarget::Spread,
+        ModTarget::Spread => ModTarget::Pitch,
+        ModTarget::Pitch => ModTarget::Gain,
+        ModTarget::Gain => ModTarget::Off,
+    }
+}
+
+fn mod_target_from_preset(value: u8) -> ModTarget {
+    match value {
+        0 => ModTarget::Off,
+        1 => ModTarget::LoopStart,
+        2 => ModTarget::LoopLength,
+        3 => ModTarget::Density,
+        4 => ModTarget::GrainLength,
+        5 => ModTarget::Spray,
+        6 => ModTarget::Pan,
+        7 => ModTarget::Spread,
+        8 => ModTarget::Pitch,
+        _ => ModTarget::Gain,
+    }
+}
+
+fn next_division(division: Option<Division>) -> Option<Division> {
+    match division {
+        None => Some(Division::FourBars),
+        Some(Division::FourBars) => Some(Division::TwoBars),
+        Some(Division::TwoBars) => Some(Division::Bar),
+        Some(Division::Bar) => Some(Division::Half),
+        Some(Division::Half) => Some(Division::Quarter),
+        Some(Division::Quarter) => Some(Division::Eighth),
+        Some(Division::Eighth) => Some(Division::Sixteenth),
+        Some(Division::Sixteenth) => Some(Division::ThirtySecond),
+        Some(Division::ThirtySecond) => None,
+    }
+}
+
+/// 0 is unsynced, 1 to 8 go from four bars down to a thirty-second note.
+fn division_from_preset(value: u8) -> Option<Division> {
+    match value {
+        0 => None,
+        1 => Some(Division::FourBars),
+        2 => Some(Division::TwoBars),
+        3 => Some(Division::Bar),
+        4 => Some(Division::Half),
+        5 => Some(Division::Quarter),
+        6 => Some(Division::Eighth),
+        7 => Some(Division::Sixteenth),
+        _ => Some(Division::ThirtySecond),
+    }
+}
+
+fn filter_mode_from_preset(value: u8) -> FilterMode {
+    match value {
+        0 => FilterMode::Off,
+        1 => FilterMode::LowPass,
+        2 => FilterMode::BandPass,
+        _ => FilterMode::HighPass,
+    }
+}
@@ -0,0 +1,120 @@
+use std::f32::consts::TAU;
+
+use crate::tempo::Division;
+
+/// A parameter of an instance that can be modulated by the mod wheel or an
+/// LFO.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum ModTarget {
+    #[default]
+    Off,
+    LoopStart,
+    LoopLength,
+    /// Scales the density by up to two octaves.
+    Density,
+    /// Scales the grain length by up to two octaves.
+    GrainLength,
+    Spray,
+    Pan,
+    Spread,
+    /// Transposes by up to an octave, smoothed like the pitch bend.
+    Pitch,
+    Gain,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    /// Jumps to a new random value every cycle.
+    SampleAndHold,
+    /// Glides to a new random value every cycle.
+    SmoothRandom,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LfoSettings {
+    pub shape: LfoShape,
+    pub target: ModTarget,
+    /// Cycles per second, unless the LFO is synced.
+    pub rate: f32,
+    /// From -1 to 1.
+    pub depth: f32,
+    /// The length of a cycle when synced to the tempo.
+    pub sync: Option<Division>,
+}
+
+impl Default for LfoSettings {
+    fn default() -> Self {
+        Self {
+            shape: LfoShape::Sine,
+            target: ModTarget::Off,
+            rate: 1.0,
+            depth: 0.5,
+            sync: None,
+        }
+    }
+}
+
+/// The values of the modulation targets of an instance after modulation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Modulated {
+    pub loop_start: f32,
+    pub loop_length: f32,
+    pub density: f32,
+    pub grain_length: f32,
+    pub spray: f32,
+    pub pan: f32,
+    pub spread: f32,
+    /// In semitones, including the pitch bend.
+    pub pitch: f32,
+    pub gain: f32,
+}
+
+pub(crate) struct Lfo {
+    phase: f32,
+    previous: f32,
+    next: f32,
+    pub value: f32,
+    rng: fastrand::Rng,
+}
+
+impl Lfo {
+    pub fn new() -> Self {
+        let mut rng = fastrand::Rng::with_seed(fastrand::u64(..));
+        Self {
+            phase: 0.0,
+            previous: 0.0,
+            next: rng.f32() * 2.0 - 1.0,
+            value: 0.0,
+            rng,
+        }
+    }
+
+    /// Moves the phase on by `phase_inc` cycles and updates the value.
+    pub fn advance(&mut self, shape: LfoShape, phase_inc: f32) {
+        self.phase += phase_inc;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.previous = self.next;
+            self.next = self.rng.f32() * 2.0 - 1.0;
+        }
+
+        let phase = self.phase;
+        self.value = match shape {
+            LfoShape::Sine => (phase * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * ((phase + 0.25).fract() - 0.5).abs(),
+            LfoShape::Saw => phase * 2.0 - 1.0,
+            LfoShape::Square if phase < 0.5 => 1.0,
+            LfoShape::Square => -1.0,
+            LfoShape::SampleAndHold => self.previous,
+            LfoShape::SmoothRandom => {
+                let t = phase * phase * (3.0 - 2.0 * phase);
+                self.previous + (self.next - self.previous) * t
+            }
+        };
+    }
+}
@@ -0,0 +1,72 @@
+use std::f32::consts::PI;
+
+const HALF_TAPS: usize = 16;
+const TABLE_RESOLUTION: usize = 512;
+
+/// Converts `samples` from one sample rate to another with a windowed sinc
+/// filter. The cutoff is lowered when downsampling to avoid aliasing. This
+/// allocates and is meant to be used outside of the audio thread.
+pub fn resample(samples: &[f32], from: f32, to: f32) -> Vec<f32> {
+    if from == to || samples.is_empty() || from <= 0.0 || to <= 0.0 {
+        return samples.to_vec();
+    }
+
+    let ratio = from as f64 / to as f64;
+    let cutoff = (1.0 / ratio).min(1.0);
+    let half_width = HALF_TAPS as f64 / cutoff;
+    let kernel = kernel_table();
+
+    let out_len = (samples.len() as f64 / ratio).round() as usize;
+    let mut output = Vec::with_capacity(out_len);
+
+    for n in 0..out_len {
+        let pos = n as f64 * ratio;
+        let start = ((pos - half_width).ceil().max(0.0)) as usize;
+        let end = ((pos + half_width).floor() as usize).min(samples.len() - 1);
+
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        for (i, sample) in samples.iter().enumerate().take(end + 1).skip(start) {
+            let x = ((i as f64 - pos).abs() * cutoff) as f32;
+            let weight = read_kernel(&kernel, x);
+            sum += sample * weight;
+            weight_sum += weight;
+        }
+
+        output.push(if weight_sum > 0.0 {
+            sum / weight_sum
+        } else {
+            0.0
+        });
+    }
+
+    output
+}
+
+fn kernel_table() -> Vec<f32> {
+    (0..=HALF_TAPS * TABLE_RESOLUTION)
+        .map(|i| {
+            let x = i as f32 / TABLE_RESOLUTION as f32;
+            let sinc = if i == 0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let window = 0.42
+                + 0.5 * (PI * x / HALF_TAPS as f32).cos()
+                + 0.08 * (2.0 * PI * x / HALF_TAPS as f32).cos();
+            sinc * window
+        })
+        .collect()
+}
+
+fn read_kernel(kernel: &[f32], x: f32) -> f32 {
+    let pos = x * TABLE_RESOLUTION as f32;
+    let index = pos as usize;
+    if index + 1 >= kernel.len() {
+        return 0.0;
+    }
+
+    let frac = pos - index as f32;
+    kernel[index] + (kernel[index + 1] - kernel[index]) * frac
+}
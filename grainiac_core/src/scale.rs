@@ -0,0 +1,53 @@
+/// The pitches that randomized grains are quantized to.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Scale {
+    /// No quantization, grains are transposed continuously.
+    #[default]
+    Free,
+    Chromatic,
+    Major,
+    Minor,
+    Pentatonic,
+    WholeTone,
+    /// Octaves and fifths only.
+    Fifths,
+    /// The intervals set with `Sampler::set_custom_scale`.
+    Custom,
+}
+
+impl Scale {
+    /// The pitch classes of the scale as bits, the lowest bit being the root.
+    /// `custom` is used for `Scale::Custom` and `None` means no quantization.
+    pub fn pitch_classes(self, custom: u16) -> Option<u16> {
+        match self {
+            Scale::Free => None,
+            Scale::Chromatic => Some(0b1111_1111_1111),
+            Scale::Major => Some(0b1010_1011_0101),
+            Scale::Minor => Some(0b0101_1010_1101),
+            Scale::Pentatonic => Some(0b0010_1001_0101),
+            Scale::WholeTone => Some(0b0101_0101_0101),
+            Scale::Fifths => Some(0b0000_1000_0001),
+            Scale::Custom => (custom & 0b1111_1111_1111 != 0).then_some(custom),
+        }
+    }
+}
+
+/// Turns a list of intervals in semitones into pitch class bits. Intervals
+/// of an octave or more wrap around.
+pub fn pitch_classes_from_intervals(intervals: &[u8]) -> u16 {
+    intervals
+        .iter()
+        .fold(0, |classes, interval| classes | 1 << (interval % 12))
+}
+
+/// Rounds `semitones` to the nearest pitch within `pitch_classes`.
+pub fn quantize(semitones: f32, pitch_classes: u16) -> f32 {
+    let center = semitones.round() as i32;
+    (center - 6..=center + 6)
+        .filter(|note| pitch_classes & 1 << note.rem_euclid(12) != 0)
+        .min_by(|a, b| {
+            let distance = |note: &i32| (*note as f32 - semitones).abs();
+            distance(a).total_cmp(&distance(b))
+        })
+        .map_or(semitones, |note| note as f32)
+}
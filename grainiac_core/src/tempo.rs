@@ -0,0 +1,32 @@
+/// A note length, used to sync rates to the tempo. Assumes 4/4.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Division {
+    FourBars,
+    TwoBars,
+    Bar,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+impl Division {
+    pub fn beats(self) -> f32 {
+        match self {
+            Division::FourBars => 16.0,
+            Division::TwoBars => 8.0,
+            Division::Bar => 4.0,
+            Division::Half => 2.0,
+            Division::Quarter => 1.0,
+            Division::Eighth => 0.5,
+            Division::Sixteenth => 0.25,
+            Division::ThirtySecond => 0.125,
+        }
+    }
+
+    /// The length of the division in seconds at `tempo` in beats per minute.
+    pub fn seconds(self, tempo: f32) -> f32 {
+        self.beats() * 60.0 / tempo
+    }
+}
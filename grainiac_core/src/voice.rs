@@ -1,22 +1,121 @@
 use std::{cmp::Ordering, ops::AddAssign};
 
 use crate::{
-    constants::{BUFFER_SIZE_SECONDS_RECORD, GRAIN_NUM},
+    config::SamplerConfig,
+    constants::{
+        BEND_SMOOTHING_SECONDS, DEFAULT_CUTOFF, STEAL_FADE_SECONDS, STRETCH_GRAIN_SECONDS,
+    },
+    filter::{FilterMode, Svf},
     grain::GrainData,
     instance::Mode,
+    scale,
+    window::GrainWindow,
 };
 
 use super::grain::Grain;
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, Default)]
 pub enum PlayDirection {
+    #[default]
     Forward,
     Backward,
+    /// Turns around at the ends of the loop, or halfway through a grain.
+    BackAndForth,
 }
 
-impl Default for PlayDirection {
+/// How the onsets of grains are spaced in time.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum GrainScheduling {
+    /// Evenly spaced onsets, which sound pitched at higher densities.
+    #[default]
+    Synchronous,
+    /// Poisson distributed onsets at the same average density.
+    Asynchronous,
+    /// Evenly spaced onsets that are randomly moved by the jitter amount.
+    Jittered,
+}
+
+/// Decides which voice makes room for a new note once all voices of an
+/// instance are playing.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum VoiceStealing {
+    /// Steals the voice that started first.
+    #[default]
+    Oldest,
+    /// Steals the voice with the lowest envelope level.
+    Quietest,
+    /// Retriggers a voice that already plays the same note, even if other
+    /// voices are free. Falls back to the oldest voice.
+    SameNote,
+    /// Keeps the lowest notes. A new note only steals the highest playing
+    /// note if it is lower.
+    LowestPriority,
+    /// Keeps the highest notes. A new note only steals the lowest playing
+    /// note if it is higher.
+    HighestPriority,
+}
+
+/// Maps the note velocity to the gain of a voice.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum VelocityCurve {
+    #[default]
+    Linear,
+    /// Loud notes are easier to reach.
+    Soft,
+    /// Loud notes need more force.
+    Hard,
+    /// Every note plays at full velocity.
+    Fixed,
+}
+
+impl VelocityCurve {
+    pub fn apply(self, velocity: f32) -> f32 {
+        let velocity = velocity.clamp(0.0, 1.0);
+        match self {
+            VelocityCurve::Linear => velocity,
+            VelocityCurve::Soft => velocity.sqrt(),
+            VelocityCurve::Hard => velocity * velocity,
+            VelocityCurve::Fixed => 1.0,
+        }
+    }
+}
+
+/// Per-note expression as sent by MPE controllers or CLAP hosts.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum NoteExpression {
+    /// Offset from the note's pitch in semitones.
+    Tuning(f32),
+    /// From 0 to 1, raises the gain of the note by up to 6 dB.
+    Pressure(f32),
+    /// From 0 to 1, centred at 0.5. Scales the density by up to an octave
+    /// and shifts the spray.
+    Brightness(f32),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Expression {
+    tuning: f32,
+    pressure: f32,
+    brightness: f32,
+}
+
+impl Expression {
+    pub fn apply(&mut self, expression: NoteExpression) {
+        match expression {
+            NoteExpression::Tuning(tuning) => self.tuning = tuning,
+            NoteExpression::Pressure(pressure) => self.pressure = pressure.clamp(0.0, 1.0),
+            NoteExpression::Brightness(brightness) => self.brightness = brightness.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Default for Expression {
     fn default() -> Self {
-        PlayDirection::Forward
+        Self {
+            tuning: 0.0,
+            pressure: 0.0,
+            brightness: 0.5,
+        }
     }
 }
 
@@ -29,7 +128,7 @@ pub struct PlayHead {
 impl AddAssign<f32> for PlayHead {
     fn add_assign(&mut self, rhs: f32) {
         let val_int = rhs.floor();
-        let val_frac = rhs - val_int as f32;
+        let val_frac = rhs - val_int;
 
         self.index += val_int as usize;
         self.fraction = val_frac;
@@ -52,67 +151,163 @@ pub struct Voice {
     pub env: Envelope,
     pub is_playing: bool,
     pub midi_note: usize,
+    pub channel: u8,
+    pub(crate) note_order: u64,
+    pending_note: Option<(usize, f32)>,
     pub loop_start: f32,
     pub loop_length: f32,
+    loop_start_abs: f32,
+    loop_end_abs: f32,
     anti_clip: Envelope,
-    grains: [Grain; GRAIN_NUM],
+    grains: Vec<Grain>,
     grain_trigger: Trigger,
     play_dircetion: PlayDirection,
     grain_dircetion: PlayDirection,
     buffersize: usize,
     pub play_pos: f32,
+    is_reversed: bool,
+    live_pos: Option<f32>,
     pub play_head: PlayHead,
     speed: f32,
     sample_rate: f32,
     pitch: f32,
     global_pitch: i8,
+    main_pitch: f32,
+    pitch_spray: f32,
+    pitch_classes: Option<u16>,
+    reverse_probability: f32,
+    filter_mode: FilterMode,
+    cutoff: f32,
+    resonance: f32,
+    cutoff_spray: f32,
+    resonance_spray: f32,
+    bend: f32,
+    bend_current: f32,
+    bend_ratio: f32,
+    bend_coeff: f32,
+    expression: Expression,
+    velocity: f32,
+    velocity_to_density: f32,
+    velocity_to_spray: f32,
+    velocity_to_attack: f32,
+    attack: f32,
     pub gain: f32,
+    density: f32,
     spray: f32,
     spread: f32,
     pan: f32,
     grain_length: f32,
-    grain_data: Vec<GrainData>,
+    is_stretch: bool,
+    stretch_grain_length: usize,
+    stretch_counter: usize,
+    rng: fastrand::Rng,
 }
 
 impl Voice {
-    pub fn new(sample_rate: f32, loop_area: (f32, f32)) -> Self {
-        let buffersize = (BUFFER_SIZE_SECONDS_RECORD * sample_rate) as usize;
+    pub fn new(config: &SamplerConfig, loop_area: (f32, f32)) -> Self {
+        let sample_rate = config.sample_rate;
+        let buffersize = config.record_buffer_size();
         let speed = 1.0 / buffersize as f32;
-        Self {
-            grains: [Grain::default(); GRAIN_NUM],
-            grain_trigger: Trigger::new(48000.0, 10.0),
+        let mut voice = Self {
+            grains: vec![Grain::default(); config.grain_num],
+            grain_trigger: Trigger::new(sample_rate, 10.0),
             play_dircetion: PlayDirection::Forward,
             grain_dircetion: PlayDirection::Forward,
             env: Envelope::new(sample_rate),
             anti_clip: Envelope::from(sample_rate, 0.001, 0.001, EnvelopeState::Attack),
             is_playing: false,
             midi_note: 0,
+            channel: 0,
+            note_order: 0,
+            pending_note: None,
             buffersize,
             play_pos: 0.25,
+            is_reversed: false,
+            live_pos: None,
             play_head: PlayHead::default(),
             loop_start: loop_area.0,
             loop_length: loop_area.1,
+            loop_start_abs: 0.0,
+            loop_end_abs: 0.0,
             speed,
             sample_rate,
             pitch: 1.0,
             global_pitch: 0,
+            main_pitch: 1.0,
+            pitch_spray: 0.0,
+            pitch_classes: None,
+            reverse_probability: 0.0,
+            filter_mode: FilterMode::Off,
+            cutoff: DEFAULT_CUTOFF,
+            resonance: 0.0,
+            cutoff_spray: 0.0,
+            resonance_spray: 0.0,
+            bend: 0.0,
+            bend_current: 0.0,
+            bend_ratio: 1.0,
+            bend_coeff: smoothing_coeff(sample_rate),
+            expression: Expression::default(),
+            velocity: 1.0,
+            velocity_to_density: 0.0,
+            velocity_to_spray: 0.0,
+            velocity_to_attack: 0.0,
+            attack: 1.0,
             gain: 0.0,
+            density: 10.0,
             grain_length: 0.25,
-            grain_data: vec![GrainData::default(); GRAIN_NUM],
+            is_stretch: false,
+            stretch_grain_length: (STRETCH_GRAIN_SECONDS * sample_rate) as usize,
+            stretch_counter: 0,
+            rng: fastrand::Rng::with_seed(fastrand::u64(..)),
             spread: 1.0,
             spray: 0.0,
             pan: 0.0,
+        };
+        voice.update_loop_bounds();
+        voice
+    }
+
+    /// Rescales everything that is counted in samples to a new sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let ratio = sample_rate / self.sample_rate;
+
+        self.play_pos *= ratio;
+        self.grain_trigger.set_sample_rate(sample_rate);
+        self.env.rescale(1.0 / ratio);
+        self.anti_clip.rescale(1.0 / ratio);
+        self.stretch_grain_length = (STRETCH_GRAIN_SECONDS * sample_rate) as usize;
+        self.bend_coeff = smoothing_coeff(sample_rate);
+        self.sample_rate = sample_rate;
+
+        for grain in self.grains.iter_mut() {
+            grain.reset();
         }
     }
 
     pub fn resize(&mut self, buffersize: usize) {
         self.buffersize = buffersize;
+        self.update_loop_bounds();
+    }
+
+    fn update_loop_bounds(&mut self) {
+        self.loop_start_abs = self.loop_start * self.buffersize as f32;
+        self.loop_end_abs = ((self.loop_start + self.loop_length) * self.buffersize as f32)
+            .clamp(0.0, self.buffersize as f32);
     }
 
     pub fn set_play_direction(&mut self, play_direction: PlayDirection) {
         self.play_dircetion = play_direction;
     }
 
+    /// The direction the play head is currently moving in.
+    fn head_direction(&self) -> PlayDirection {
+        match self.play_dircetion {
+            PlayDirection::BackAndForth if self.is_reversed => PlayDirection::Backward,
+            PlayDirection::BackAndForth => PlayDirection::Forward,
+            direction => direction,
+        }
+    }
+
     pub fn set_grain_direction(&mut self, grain_direction: PlayDirection) {
         self.grain_dircetion = grain_direction;
     }
@@ -121,16 +316,41 @@ impl Voice {
         self.speed = speed;
     }
 
+    /// Pins the play head to `pos` in samples instead of moving it through
+    /// the loop, or releases it with `None`.
+    pub fn set_live_pos(&mut self, pos: Option<f32>) {
+        self.live_pos = pos;
+    }
+
     pub fn set_loop_start(&mut self, loop_start: f32) {
         self.loop_start = loop_start;
+        self.update_loop_bounds();
     }
 
     pub fn set_loop_length(&mut self, loop_length: f32) {
         self.loop_length = loop_length;
+        self.update_loop_bounds();
     }
 
     pub fn set_density(&mut self, density: f32) {
-        self.grain_trigger.set_freq(density);
+        self.density = density;
+        self.update_density();
+    }
+
+    fn update_density(&mut self) {
+        let scale = 2.0f32.powf(self.expression.brightness * 2.0 - 1.0)
+            * (1.0 + self.velocity_to_density * self.velocity);
+        self.grain_trigger.set_freq(self.density * scale);
+    }
+
+    pub fn set_scheduling(&mut self, scheduling: GrainScheduling) {
+        self.grain_trigger.scheduling = scheduling;
+    }
+
+    /// Sets how far jittered onsets can move, from 0 to 1 of the distance
+    /// between two grains.
+    pub fn set_jitter(&mut self, jitter: f32) {
+        self.grain_trigger.jitter = jitter.clamp(0.0, 1.0);
     }
 
     pub fn set_spray(&mut self, spray: f32) {
@@ -149,46 +369,234 @@ impl Voice {
         self.grain_length = grain_length;
     }
 
+    /// In tape mode, decouples the playhead from the pitch of the note. The
+    /// playhead then moves at the play speed and the audio is transposed by
+    /// overlapping grains instead.
+    pub fn set_stretch(&mut self, is_stretch: bool) {
+        self.is_stretch = is_stretch;
+    }
+
     pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+        self.update_attack();
+    }
+
+    fn update_attack(&mut self) {
+        let attack = self.attack * (1.0 - self.velocity_to_attack * self.velocity);
         self.env.inc_attack = 1.0 / (self.sample_rate * attack);
     }
 
+    /// Raises the density by up to an octave for loud notes.
+    pub fn set_velocity_to_density(&mut self, amount: f32) {
+        self.velocity_to_density = amount.clamp(0.0, 1.0);
+        self.update_density();
+    }
+
+    /// Adds up to the full spray range for loud notes.
+    pub fn set_velocity_to_spray(&mut self, amount: f32) {
+        self.velocity_to_spray = amount.clamp(0.0, 1.0);
+    }
+
+    /// Shortens the attack down to zero for loud notes.
+    pub fn set_velocity_to_attack(&mut self, amount: f32) {
+        self.velocity_to_attack = amount.clamp(0.0, 1.0);
+        self.update_attack();
+    }
+
     pub fn set_release(&mut self, release: f32) {
         self.env.inc_release = 1.0 / (self.sample_rate * release);
     }
 
+    /// Sets how far grains are randomly transposed, in semitones.
+    pub fn set_pitch_spray(&mut self, semitones: f32) {
+        self.pitch_spray = semitones;
+    }
+
+    /// Quantizes the random transposition to pitch class bits, or leaves it
+    /// continuous with `None`.
+    pub fn set_pitch_classes(&mut self, pitch_classes: Option<u16>) {
+        self.pitch_classes = pitch_classes;
+    }
+
+    fn grain_pitch(&mut self) -> f32 {
+        if self.pitch_spray == 0.0 {
+            return 1.0;
+        }
+
+        let semitones = self.pitch_spray * (self.rng.f32() * 2.0 - 1.0);
+        let semitones = match self.pitch_classes {
+            Some(pitch_classes) => scale::quantize(semitones, pitch_classes),
+            None => semitones,
+        };
+        2.0f32.powf(semitones / 12.0)
+    }
+
+    /// Sets the chance from 0 to 1 that a grain plays against the grain
+    /// direction.
+    pub fn set_reverse_probability(&mut self, probability: f32) {
+        self.reverse_probability = probability;
+    }
+
+    fn next_grain_direction(&mut self) -> PlayDirection {
+        if self.reverse_probability == 0.0 || self.rng.f32() >= self.reverse_probability {
+            return self.grain_dircetion;
+        }
+
+        match self.grain_dircetion {
+            PlayDirection::Forward => PlayDirection::Backward,
+            PlayDirection::Backward => PlayDirection::Forward,
+            PlayDirection::BackAndForth => PlayDirection::BackAndForth,
+        }
+    }
+
+    pub fn set_filter_mode(&mut self, mode: FilterMode) {
+        self.filter_mode = mode;
+    }
+
+    /// Sets the centre cutoff of the grain filter in Hz.
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff;
+    }
+
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance;
+    }
+
+    /// Sets how far the cutoff of each grain is randomly moved, in octaves.
+    pub fn set_cutoff_spray(&mut self, octaves: f32) {
+        self.cutoff_spray = octaves;
+    }
+
+    /// Sets how far the resonance of each grain is randomly moved, from 0 to 1.
+    pub fn set_resonance_spray(&mut self, amount: f32) {
+        self.resonance_spray = amount;
+    }
+
+    fn grain_filter(&mut self) -> Svf {
+        if self.filter_mode == FilterMode::Off {
+            return Svf::default();
+        }
+
+        let cutoff = self.cutoff * 2.0f32.powf(self.cutoff_spray * (self.rng.f32() * 2.0 - 1.0));
+        let resonance = self.resonance + self.resonance_spray * (self.rng.f32() * 2.0 - 1.0);
+        Svf::new(self.filter_mode, cutoff, resonance, self.sample_rate)
+    }
+
+    /// Runs a sample read for the grain in `slot` through the grain's filter.
+    pub fn filter_grain(&mut self, slot: usize, sample: (f32, f32)) -> (f32, f32) {
+        self.grains[slot].filter(sample)
+    }
+
     pub fn set_global_pitch(&mut self, global_pitch: i8) {
         self.global_pitch = global_pitch;
+        self.main_pitch = 2.0f32.powf(global_pitch as f32 / 12.0);
+    }
+
+    /// Retunes the voice and its running grains by `semitones`. The change is
+    /// smoothed to avoid zipper noise.
+    pub fn set_bend(&mut self, semitones: f32) {
+        self.bend = semitones;
     }
 
-    pub fn note_on(&mut self, midi_note: usize) {
-        let loop_start_abs = self.loop_start * self.buffersize as f32;
+    fn update_bend(&mut self) {
+        if self.bend_current == self.bend {
+            return;
+        }
+
+        self.bend_current += (self.bend - self.bend_current) * self.bend_coeff;
+        if (self.bend - self.bend_current).abs() < 1e-4 {
+            self.bend_current = self.bend;
+        }
+        self.bend_ratio = 2.0f32.powf(self.bend_current / 12.0);
+    }
+
+    /// Replaces all expressions of the voice, e.g. with the state of its
+    /// MPE channel before a note starts. The tuning is picked up by the next
+    /// note on.
+    pub fn set_expressions(&mut self, expression: Expression) {
+        self.expression = expression;
+        self.update_density();
+    }
+
+    pub fn set_expression(&mut self, expression: NoteExpression) {
+        self.expression.apply(expression);
+        match expression {
+            NoteExpression::Tuning(_) => self.update_pitch(),
+            NoteExpression::Brightness(_) => self.update_density(),
+            NoteExpression::Pressure(_) => {}
+        }
+    }
+
+    fn update_pitch(&mut self) {
+        let note = self.midi_note as f32 + self.expression.tuning;
+        self.pitch = 2.0f32.powf((note - 60.0) / 12.0);
+    }
+
+    /// Starts a note. `velocity` is expected to be shaped by the velocity
+    /// curve of the instance already.
+    pub fn note_on(&mut self, midi_note: usize, velocity: f32) {
         self.is_playing = true;
         self.midi_note = midi_note;
-        self.pitch = 2.0f32.powf((midi_note as f32 - 60.0) / 12.0);
-        self.play_pos = loop_start_abs as f32;
+        self.velocity = velocity;
+        self.update_pitch();
+        self.update_density();
+        self.update_attack();
+        // a new note starts at the current bend instead of gliding to it
+        self.bend_current = self.bend;
+        self.bend_ratio = 2.0f32.powf(self.bend / 12.0);
+        self.play_pos = self.loop_start_abs;
+        self.is_reversed = false;
+        self.stretch_counter = 0;
         self.env.set_state(EnvelopeState::Attack);
     }
 
     pub fn note_off(&mut self) {
-        self.env.set_state(EnvelopeState::Release);
+        // a note released while its voice is still fading out for it is
+        // dropped, the fade then simply runs to the end
+        if self.pending_note.take().is_none() {
+            self.env.set_state(EnvelopeState::Release);
+        }
+    }
+
+    /// Quickly fades out the current note and starts `midi_note` as soon as
+    /// the voice is silent, so that stealing a voice doesn't click.
+    pub fn steal(&mut self, midi_note: usize, velocity: f32) {
+        self.midi_note = midi_note;
+        self.pending_note = Some((midi_note, velocity));
+        self.env.set_state(EnvelopeState::Steal);
+    }
+
+    /// How far the tape play head moves per sample.
+    pub fn tape_speed(&self) -> f32 {
+        self.pitch * self.bend_ratio
+    }
+
+    /// Whether the voice is fading out to make room for a pending note.
+    pub fn is_stealing(&self) -> bool {
+        self.pending_note.is_some()
     }
 
     pub fn is_release(&self) -> bool {
         self.env.state == EnvelopeState::Release
     }
 
-    pub fn render(&mut self, mode: Mode) -> Vec<GrainData> {
-        let loop_start_abs = (self.loop_start * self.buffersize as f32) as f32;
-        let loop_end_abs = ((self.loop_start + self.loop_length) * self.buffersize as f32)
-            .clamp(0.0, self.buffersize as f32) as f32;
+    /// Advances the voice by one sample and appends the state of its active
+    /// grains to `grain_data`, which has to be preallocated by the caller.
+    pub fn render(&mut self, mode: Mode, window: &GrainWindow, grain_data: &mut Vec<GrainData>) {
+        let loop_start_abs = self.loop_start_abs;
+        let loop_end_abs = self.loop_end_abs;
+        let is_stretching = mode == Mode::Tape && self.is_stretch;
+        self.update_bend();
 
-        match self.play_dircetion {
-            PlayDirection::Forward => {
-                self.play_pos = match mode {
-                    Mode::Grain => self.play_pos + self.speed as f32,
-                    Mode::Tape => self.play_pos + (1.0 * self.pitch as f32),
-                };
+        let step = match mode {
+            Mode::Tape if !is_stretching => self.pitch * self.bend_ratio,
+            _ => self.speed,
+        };
+
+        match (self.live_pos, self.play_dircetion) {
+            (Some(pos), _) => self.play_pos = pos,
+            (None, PlayDirection::Forward) => {
+                self.play_pos += step;
 
                 if self.play_pos >= loop_end_abs - 10.0
                     && self.anti_clip.state != EnvelopeState::Release
@@ -201,11 +609,8 @@ impl Voice {
                     self.anti_clip.state = EnvelopeState::Attack;
                 }
             }
-            PlayDirection::Backward => {
-                self.play_pos = match mode {
-                    Mode::Grain => self.play_pos - self.speed as f32,
-                    Mode::Tape => self.play_pos - (1.0 * self.pitch) as f32,
-                };
+            (None, PlayDirection::Backward) => {
+                self.play_pos -= step;
 
                 if self.play_pos <= loop_start_abs + 10.0
                     && self.anti_clip.state != EnvelopeState::Release
@@ -218,47 +623,95 @@ impl Voice {
                     self.anti_clip.state = EnvelopeState::Attack;
                 }
             }
+            (None, PlayDirection::BackAndForth) => {
+                // the head turns around instead of jumping, so no fade is needed
+                if self.is_reversed {
+                    self.play_pos -= step;
+                } else {
+                    self.play_pos += step;
+                }
+
+                if self.play_pos >= loop_end_abs {
+                    self.play_pos = loop_end_abs;
+                    self.is_reversed = true;
+                } else if self.play_pos <= loop_start_abs {
+                    self.play_pos = loop_start_abs;
+                    self.is_reversed = false;
+                }
+            }
         }
 
         if self.grain_trigger.update() && mode == Mode::Grain {
+            let spray =
+                (self.spray + self.expression.brightness + self.velocity_to_spray * self.velocity
+                    - 0.5)
+                    .clamp(0.0, 1.0);
+            let grain_pitch = self.grain_pitch();
+            let grain_direction = self.next_grain_direction();
+            let grain_filter = self.grain_filter();
             for grain in self.grains.iter_mut() {
                 let mut pos = self.play_pos
-                    + (self.spray * ((fastrand::f32() * self.sample_rate) - self.sample_rate * 0.5))
-                        as f32;
+                    + spray * ((self.rng.f32() * self.sample_rate) - self.sample_rate * 0.5);
 
                 if pos < 0.0 {
-                    pos = self.buffersize as f32 + pos;
+                    pos += self.buffersize as f32;
                 } else if pos > self.buffersize as f32 {
-                    pos = pos - self.buffersize as f32;
+                    pos -= self.buffersize as f32;
                 }
 
-                let main_pitch = 2.0f32.powf(self.global_pitch as f32 / 12.0);
-
                 if !grain.active {
-                    let stereo_pos = self.pan + self.spread * ((fastrand::f32() * 2.0) - 1.0);
+                    let stereo_pos = self.pan + self.spread * ((self.rng.f32() * 2.0) - 1.0);
                     grain.activate(
                         (self.sample_rate * self.grain_length) as usize,
-                        pos as f32,
-                        self.pitch * main_pitch,
+                        pos,
+                        self.pitch * self.main_pitch * grain_pitch,
                         self.buffersize,
                         stereo_pos.clamp(-1.0, 1.0),
-                        self.grain_dircetion.clone(),
+                        grain_direction,
                     );
+                    grain.set_filter(grain_filter);
                     break;
                 }
             }
         }
 
-        self.grain_data.clear();
-        for grain in self.grains.iter_mut() {
+        if is_stretching {
+            if self.stretch_counter == 0 {
+                let direction = self.head_direction();
+                if let Some(grain) = self.grains.iter_mut().find(|grain| !grain.active) {
+                    grain.activate(
+                        self.stretch_grain_length,
+                        self.play_pos,
+                        self.pitch * self.main_pitch,
+                        self.buffersize,
+                        self.pan.clamp(-1.0, 1.0),
+                        direction,
+                    );
+                }
+            }
+            self.stretch_counter = (self.stretch_counter + 1) % (self.stretch_grain_length / 2);
+        }
+
+        // stretched grains overlap by half their length and are panned like
+        // regular grains, so they are doubled to match the level of plain tape
+        let grain_gain = if is_stretching {
+            self.gain * 2.0
+        } else {
+            self.gain
+        };
+
+        for (slot, grain) in self.grains.iter_mut().enumerate() {
             if grain.active {
-                self.grain_data.push(grain.update(self.gain));
+                grain_data.push(GrainData {
+                    grain: slot,
+                    ..grain.update(grain_gain, self.bend_ratio, window)
+                });
             }
         }
 
         let clip_gain = self.anti_clip.update();
-        let env = self.env.update();
-        if mode == Mode::Tape {
+        let env = self.env.update() * (1.0 + self.expression.pressure) * self.velocity;
+        if mode == Mode::Tape && !is_stretching {
             self.gain = env * clip_gain;
         } else {
             self.gain = env;
@@ -271,17 +724,30 @@ impl Voice {
             for grain in self.grains.iter_mut() {
                 grain.reset();
             }
-        }
 
-        self.grain_data.clone()
+            if let Some((midi_note, velocity)) = self.pending_note.take() {
+                self.note_on(midi_note, velocity);
+            }
+        }
     }
 }
 
+fn smoothing_coeff(sample_rate: f32) -> f32 {
+    1.0 - (-1.0 / (sample_rate * BEND_SMOOTHING_SECONDS)).exp()
+}
+
 struct Trigger {
     phase: f32,
     speedrement: f32,
+    /// Phase of the next onset, 1 for evenly spaced grains.
+    onset: f32,
+    /// How far the next jittered onset is moved from its grid point.
+    offset: f32,
     is_reset: bool,
     sample_rate: f32,
+    scheduling: GrainScheduling,
+    jitter: f32,
+    rng: fastrand::Rng,
 }
 
 impl Trigger {
@@ -289,23 +755,48 @@ impl Trigger {
         Self {
             phase: 0.0,
             speedrement: frequency / sample_rate,
+            onset: 1.0,
+            offset: 0.0,
             is_reset: true,
             sample_rate,
+            scheduling: GrainScheduling::Synchronous,
+            jitter: 0.5,
+            rng: fastrand::Rng::with_seed(fastrand::u64(..)),
         }
     }
 
     fn update(&mut self) -> bool {
         if self.is_reset {
             self.is_reset = false;
+            self.offset = 0.0;
+            self.onset = self.next_onset();
             return true;
         }
 
-        self.phase = self.phase + self.speedrement;
-        if self.phase >= 1.0 {
-            self.phase = 0.0;
+        self.phase += self.speedrement;
+        if self.phase >= self.onset {
+            self.phase = match self.scheduling {
+                // counts from the grid point of this onset, so the jitter
+                // never adds up and the onsets stay on the grid
+                GrainScheduling::Jittered => self.phase - (self.onset - self.offset),
+                _ => 0.0,
+            };
+            self.onset = self.next_onset();
             return true;
         }
-        return false;
+        false
+    }
+
+    fn next_onset(&mut self) -> f32 {
+        match self.scheduling {
+            GrainScheduling::Synchronous => 1.0,
+            // exponentially distributed with a mean of one period
+            GrainScheduling::Asynchronous => -(1.0 - self.rng.f32()).ln(),
+            GrainScheduling::Jittered => {
+                self.offset = self.jitter * (self.rng.f32() * 2.0 - 1.0);
+                1.0 + self.offset
+            }
+        }
     }
 
     fn reset(&mut self) {
@@ -313,6 +804,11 @@ impl Trigger {
         self.is_reset = true;
     }
 
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.speedrement *= self.sample_rate / sample_rate;
+        self.sample_rate = sample_rate;
+    }
+
     fn set_freq(&mut self, frequency: f32) {
         self.speedrement = frequency / self.sample_rate;
     }
@@ -322,6 +818,7 @@ impl Trigger {
 pub enum EnvelopeState {
     Attack,
     Release,
+    Steal,
     Hold,
     Off,
 }
@@ -329,6 +826,7 @@ pub enum EnvelopeState {
 pub struct Envelope {
     inc_attack: f32,
     inc_release: f32,
+    inc_steal: f32,
     gain: f32,
     state: EnvelopeState,
 }
@@ -338,6 +836,7 @@ impl Envelope {
         Self {
             inc_attack: 1.0 / sample_rate,
             inc_release: 1.0 / sample_rate,
+            inc_steal: 1.0 / (sample_rate * STEAL_FADE_SECONDS),
             gain: 0.0,
             state: EnvelopeState::Off,
         }
@@ -347,11 +846,18 @@ impl Envelope {
         Self {
             inc_attack: 1.0 / (sample_rate * attack),
             inc_release: 1.0 / (sample_rate * release),
+            inc_steal: 1.0 / (sample_rate * STEAL_FADE_SECONDS),
             gain: 0.0,
             state,
         }
     }
 
+    fn rescale(&mut self, factor: f32) {
+        self.inc_attack *= factor;
+        self.inc_release *= factor;
+        self.inc_steal *= factor;
+    }
+
     fn update(&mut self) -> f32 {
         match self.state {
             EnvelopeState::Attack => {
@@ -370,6 +876,14 @@ impl Envelope {
                 }
                 self.gain
             }
+            EnvelopeState::Steal => {
+                self.gain -= self.inc_steal;
+                if self.gain <= 0.000011 {
+                    self.gain = 0.0;
+                    self.state = EnvelopeState::Off;
+                }
+                self.gain
+            }
             _ => self.gain,
         }
     }
@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+const TABLE_SIZE: usize = 1024;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum WindowShape {
+    Sine,
+    Hann,
+    Gaussian,
+    Tukey,
+    Trapezoid,
+    Exponential,
+    ReverseExponential,
+}
+
+/// Tabulated grain window shared by all grains of an instance. The table is
+/// only rebuilt when the shape, flatness or skew changes.
+pub struct GrainWindow {
+    shape: WindowShape,
+    flatness: f32,
+    skew: f32,
+    table: Vec<f32>,
+}
+
+impl GrainWindow {
+    pub fn new() -> Self {
+        let mut window = Self {
+            shape: WindowShape::Sine,
+            flatness: 0.0,
+            skew: 0.0,
+            table: vec![0.0; TABLE_SIZE + 1],
+        };
+        window.update_table();
+        window
+    }
+
+    pub fn set_shape(&mut self, shape: WindowShape) {
+        if self.shape != shape {
+            self.shape = shape;
+            self.update_table();
+        }
+    }
+
+    /// Width of the flat top for `Tukey` and `Trapezoid`, from 0 (no plateau)
+    /// to 1 (rectangular).
+    pub fn set_flatness(&mut self, flatness: f32) {
+        let flatness = flatness.clamp(0.0, 1.0);
+        if self.flatness != flatness {
+            self.flatness = flatness;
+            self.update_table();
+        }
+    }
+
+    /// Moves the peak of the window towards the start (-1) or the end (1).
+    pub fn set_skew(&mut self, skew: f32) {
+        let skew = skew.clamp(-1.0, 1.0);
+        if self.skew != skew {
+            self.skew = skew;
+            self.update_table();
+        }
+    }
+
+    pub fn read(&self, phase: f32) -> f32 {
+        let pos = phase.clamp(0.0, 1.0) * TABLE_SIZE as f32;
+        let index = (pos as usize).min(TABLE_SIZE - 1);
+        let frac = pos - index as f32;
+
+        self.table[index] + (self.table[index + 1] - self.table[index]) * frac
+    }
+
+    fn update_table(&mut self) {
+        let center = 0.5 + 0.45 * self.skew;
+
+        for (i, value) in self.table.iter_mut().enumerate() {
+            let phase = i as f32 / TABLE_SIZE as f32;
+
+            let warped = if phase < center {
+                0.5 * phase / center
+            } else {
+                0.5 + 0.5 * (phase - center) / (1.0 - center)
+            };
+
+            *value = shape_at(self.shape, self.flatness, warped);
+        }
+    }
+}
+
+impl Default for GrainWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn shape_at(shape: WindowShape, flatness: f32, phase: f32) -> f32 {
+    match shape {
+        WindowShape::Sine => (PI * phase).sin(),
+        WindowShape::Hann => 0.5 - 0.5 * (2.0 * PI * phase).cos(),
+        WindowShape::Gaussian => {
+            let sigma = 0.15;
+            (-0.5 * ((phase - 0.5) / sigma).powi(2)).exp()
+        }
+        WindowShape::Tukey => {
+            let taper = (1.0 - flatness) * 0.5;
+            if taper <= f32::EPSILON {
+                1.0
+            } else if phase < taper {
+                0.5 - 0.5 * (PI * phase / taper).cos()
+            } else if phase > 1.0 - taper {
+                0.5 - 0.5 * (PI * (1.0 - phase) / taper).cos()
+            } else {
+                1.0
+            }
+        }
+        WindowShape::Trapezoid => {
+            let ramp = (1.0 - flatness) * 0.5;
+            if ramp <= f32::EPSILON {
+                1.0
+            } else {
+                (phase.min(1.0 - phase) / ramp).min(1.0)
+            }
+        }
+        WindowShape::Exponential => percussive(phase),
+        WindowShape::ReverseExponential => percussive(1.0 - phase),
+    }
+}
+
+/// Short linear attack followed by an exponential decay that reaches zero at
+/// the end of the grain.
+fn percussive(phase: f32) -> f32 {
+    let attack = 0.01;
+    let steepness: f32 = 6.0;
+
+    if phase < attack {
+        phase / attack
+    } else {
+        let x = (phase - attack) / (1.0 - attack);
+        ((-steepness * x).exp() - (-steepness).exp()) / (1.0 - (-steepness).exp())
+    }
+}
@@ -1,15 +1,941 @@
-use grainiac_core::Sampler;
+use std::f32::consts::TAU;
+
+use grainiac_core::{
+    filter::{FilterMode, Svf},
+    fx::FxChain,
+    interpolation::{Interpolation, Interpolator},
+    modulation::{LfoShape, ModTarget},
+    resample,
+    scale::{pitch_classes_from_intervals, quantize, Scale},
+    tempo::Division,
+    voice::{GrainScheduling, NoteExpression, VelocityCurve, VoiceStealing},
+    window::{GrainWindow, WindowShape},
+    Instance, Mode, Sampler, SamplerConfig,
+};
 
 #[test]
 fn play_20_seconds() {
-    let (mut sampler, _) = Sampler::new(44100.0, 1);
+    let (mut sampler, _) = Sampler::new(SamplerConfig::new(44100.0).instances(1));
     let mut data = vec![0.0; 44100 * 20];
 
-    sampler.note_on(60);
+    sampler.note_on(0, 60, 1.0);
     sampler.record(0);
     for sample in data.iter_mut() {
         let mut sample_l = *sample;
-        let mut sample_r = sample.clone();
+        let mut sample_r = *sample;
         sampler.render((&mut sample_l, &mut sample_r));
     }
 }
+
+#[test]
+fn process_20_seconds_in_blocks() {
+    let (mut sampler, _) = Sampler::new(SamplerConfig::new(44100.0).instances(2));
+    let input_l = vec![0.5; 512];
+    let input_r = vec![0.5; 512];
+    let mut output_l = vec![0.0; 512];
+    let mut output_r = vec![0.0; 512];
+
+    sampler.note_on(0, 60, 1.0);
+    sampler.record(0);
+    for _ in 0..(44100 * 20 / 512) {
+        sampler.process_block((&input_l, &input_r), (&mut output_l, &mut output_r));
+    }
+
+    assert!(output_l
+        .iter()
+        .chain(output_r.iter())
+        .all(|s| s.is_finite()));
+}
+
+/// Runs the whole render path inside a real-time context. Build with
+/// `cargo test --features rtsan` so that any allocation or lock aborts the test.
+#[cfg(feature = "rtsan")]
+#[test]
+fn render_is_realtime_safe() {
+    let (mut sampler, _) = Sampler::new(SamplerConfig::new(44100.0).instances(2));
+    let input_l = vec![0.5; 512];
+    let input_r = vec![0.5; 512];
+    let mut output_l = vec![0.0; 512];
+    let mut output_r = vec![0.0; 512];
+
+    sampler.set_density(0, 50.0);
+    sampler.set_density(1, 50.0);
+    sampler.toggle_mode(1);
+    sampler.record(0);
+    for note in 60..64 {
+        sampler.note_on(0, note, 1.0);
+    }
+
+    for _ in 0..(44100 * 12 / 512) {
+        sampler.process_block((&input_l, &input_r), (&mut output_l, &mut output_r));
+    }
+
+    for sample in output_l.iter_mut() {
+        let mut sample_r = 0.0;
+        sampler.render((sample, &mut sample_r));
+    }
+}
+
+#[test]
+fn interpolators_hit_sample_points() {
+    let interpolator = Interpolator::new();
+    let buffer: Vec<f32> = (0..64).map(|i| (i as f32 * 0.3).sin()).collect();
+
+    for interpolation in [
+        Interpolation::Linear,
+        Interpolation::Hermite,
+        Interpolation::Sinc,
+    ] {
+        for (index, sample) in buffer.iter().enumerate() {
+            let value = interpolator.read(interpolation, &buffer, index as f32, 1.0);
+            assert!((value - sample).abs() < 1e-5);
+        }
+
+        let between = interpolator.read(interpolation, &buffer, 10.5, 1.0);
+        assert!(between > buffer[10].min(buffer[11]) && between < buffer[10].max(buffer[11]));
+    }
+}
+
+#[test]
+fn sinc_interpolation_filters_before_pitching_up() {
+    let interpolator = Interpolator::new();
+    let sine = |frequency: f32| -> Vec<f32> {
+        (0..4096)
+            .map(|i| (TAU * frequency * i as f32).sin())
+            .collect()
+    };
+    // reads an octave up and measures the level of what comes out
+    let level = |buffer: &[f32], speed: f32| {
+        let output: Vec<f32> = (0..1000)
+            .map(|i| {
+                interpolator.read(Interpolation::Sinc, buffer, 100.0 + i as f32 * speed, speed)
+            })
+            .collect();
+        (output.iter().map(|s| s * s).sum::<f32>() / output.len() as f32 * 2.0).sqrt()
+    };
+
+    // 0.45 cycles per sample ends up above Nyquist an octave up and has to go
+    let high = sine(0.45);
+    assert!(level(&high, 1.0) > 0.9);
+    assert!(level(&high, 2.0) < 0.05);
+
+    let low = sine(0.05);
+    assert!(level(&low, 2.0) > 0.95);
+}
+
+#[test]
+fn grain_windows_fade_in_and_out() {
+    let mut window = GrainWindow::new();
+
+    for shape in [
+        WindowShape::Sine,
+        WindowShape::Hann,
+        WindowShape::Gaussian,
+        WindowShape::Tukey,
+        WindowShape::Trapezoid,
+        WindowShape::Exponential,
+        WindowShape::ReverseExponential,
+    ] {
+        window.set_shape(shape);
+        assert!(window.read(0.0) < 0.01);
+        assert!(window.read(1.0) < 0.01);
+    }
+
+    window.set_shape(WindowShape::Hann);
+    window.set_skew(-0.8);
+    assert!(window.read(0.25) > window.read(0.75));
+}
+
+#[test]
+fn stretched_tape_keeps_speed_when_transposing() {
+    for (is_stretch, expected_step) in [(false, 2.0), (true, 1.0)] {
+        let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+        instance.set_mode(Mode::Tape);
+        instance.set_stretch(is_stretch);
+        instance.set_play_speed(1.0);
+        instance.voices[0].note_on(72, 1.0);
+
+        let start = instance.voices[0].play_pos;
+        for _ in 0..1000 {
+            instance.render((0.0, 0.0));
+        }
+
+        let step = (instance.voices[0].play_pos - start) / 1000.0;
+        assert!((step - expected_step).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn tape_playback_keeps_stereo_image() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.load_audio([vec![0.5; 44100], vec![0.0; 44100]]);
+    instance.set_mode(Mode::Tape);
+    instance.voices[0].note_on(60, 1.0);
+
+    let mut output = (0.0, 0.0);
+    for _ in 0..1000 {
+        output = instance.render((0.0, 0.0));
+    }
+
+    assert!(output.0 > 0.0);
+    assert_eq!(output.1, 0.0);
+}
+
+#[test]
+fn resampling_keeps_duration_and_level() {
+    let samples = vec![0.5; 44100];
+    let resampled = resample(&samples, 44100.0, 48000.0);
+
+    assert_eq!(resampled.len(), 48000);
+    assert!(resampled.iter().all(|s| (s - 0.5).abs() < 1e-3));
+}
+
+#[test]
+fn changing_sample_rate_resamples_instances() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.load_audio([vec![0.5; 44100], vec![0.5; 44100]]);
+    instance.set_sample_rate(48000.0);
+
+    assert_eq!(instance.current_buffer_size, 48000);
+    assert_eq!(instance.buffer[0].len(), instance.max_buffer_size);
+}
+
+#[test]
+fn config_sets_engine_dimensions() {
+    let config = SamplerConfig::new(44100.0)
+        .instances(2)
+        .voices(4)
+        .grains_per_voice(8)
+        .bars(50)
+        .max_buffer_seconds(2.0);
+    let (mut sampler, mut draw_data) = Sampler::new(config);
+
+    let draw_data = draw_data.read();
+    assert_eq!(draw_data.len(), 2);
+    assert_eq!(draw_data[0].play_heads.len(), 4);
+    assert_eq!(draw_data[0].grain_data.len(), 32);
+    assert_eq!(draw_data[0].buffer.len(), 50);
+
+    // longer files are cut to the max buffer length
+    sampler.load_buf([vec![0.0; 44100 * 5], vec![0.0; 44100 * 5]], 0);
+    assert_eq!(sampler.get_bufs()[0][0].len(), 44100 * 2);
+}
+
+#[test]
+fn stolen_voices_fade_out_before_retriggering() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0).voices(2));
+    instance.set_voice_stealing(VoiceStealing::Oldest);
+
+    instance.note_on(0, 60, 1.0);
+    instance.note_on(0, 62, 1.0);
+    for _ in 0..44100 {
+        instance.render((0.0, 0.0));
+    }
+
+    instance.note_on(0, 64, 1.0);
+    instance.render((0.0, 0.0));
+    assert!(
+        instance.voices[0].gain > 0.9,
+        "stolen voice must not cut off"
+    );
+
+    for _ in 0..1000 {
+        instance.render((0.0, 0.0));
+    }
+    let notes: Vec<usize> = instance
+        .voices
+        .iter()
+        .map(|voice| voice.midi_note)
+        .collect();
+    assert_eq!(notes, [64, 62]);
+    assert!(
+        instance.voices[0].gain < 0.1,
+        "new note starts from silence"
+    );
+}
+
+#[test]
+fn voices_being_stolen_keep_their_pending_note() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0).voices(2));
+    instance.set_voice_stealing(VoiceStealing::Quietest);
+
+    instance.note_on(0, 60, 1.0);
+    instance.note_on(0, 62, 1.0);
+    for _ in 0..44100 {
+        instance.render((0.0, 0.0));
+    }
+
+    // the fading voice is the quietest, but its pending note must survive
+    instance.note_on(0, 64, 1.0);
+    for _ in 0..10 {
+        instance.render((0.0, 0.0));
+    }
+    instance.note_on(0, 65, 1.0);
+
+    for _ in 0..1000 {
+        instance.render((0.0, 0.0));
+    }
+    let mut notes: Vec<usize> = instance
+        .voices
+        .iter()
+        .map(|voice| voice.midi_note)
+        .collect();
+    notes.sort();
+    assert_eq!(notes, [64, 65]);
+}
+
+#[test]
+fn hold_does_not_cancel_a_steal() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0).voices(1));
+    instance.note_on(0, 60, 1.0);
+    for _ in 0..44100 {
+        instance.render((0.0, 0.0));
+    }
+
+    instance.note_on(0, 62, 1.0);
+    for _ in 0..10 {
+        instance.render((0.0, 0.0));
+    }
+    instance.toggle_hold();
+    for _ in 0..1000 {
+        instance.render((0.0, 0.0));
+    }
+
+    // the fade finishes and the queued note starts
+    assert!(!instance.voices[0].is_stealing());
+    assert_eq!(instance.voices[0].midi_note, 62);
+}
+
+#[test]
+fn note_priority_keeps_the_preferred_notes() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0).voices(2));
+    instance.set_voice_stealing(VoiceStealing::LowestPriority);
+
+    instance.note_on(0, 60, 1.0);
+    instance.note_on(0, 62, 1.0);
+    instance.note_on(0, 64, 1.0);
+    instance.note_on(0, 59, 1.0);
+    for _ in 0..1000 {
+        instance.render((0.0, 0.0));
+    }
+
+    let notes: Vec<usize> = instance
+        .voices
+        .iter()
+        .map(|voice| voice.midi_note)
+        .collect();
+    assert_eq!(notes, [60, 59]);
+}
+
+#[test]
+fn expressions_follow_their_channel_and_note() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.set_mode(Mode::Tape);
+
+    // MPE controllers send the pitch of a note before its note on
+    instance.channel_expression(2, NoteExpression::Tuning(12.0));
+    instance.note_on(2, 60, 1.0);
+    instance.note_on(3, 60, 1.0);
+    instance.note_expression(3, 60, NoteExpression::Pressure(1.0));
+
+    let start = [instance.voices[0].play_pos, instance.voices[1].play_pos];
+    for _ in 0..100 {
+        instance.render((0.0, 0.0));
+    }
+
+    assert!((instance.voices[0].play_pos - start[0] - 200.0).abs() < 0.01);
+    assert!((instance.voices[1].play_pos - start[1] - 100.0).abs() < 0.01);
+    assert!((instance.voices[1].gain / instance.voices[0].gain - 2.0).abs() < 0.01);
+}
+
+#[test]
+fn velocity_shapes_gain_and_attack() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.set_velocity_curve(VelocityCurve::Hard);
+    instance.set_velocity_to_attack(0.5);
+
+    // the default attack of one second is shortened by up to a half
+    instance.note_on(0, 60, 0.5);
+    instance.note_on(0, 62, 1.0);
+    for _ in 0..22050 {
+        instance.render((0.0, 0.0));
+    }
+
+    assert!(instance.voices[1].gain > 0.99);
+    let expected = 0.25 * 22050.0 / (0.875 * 44100.0);
+    assert!((instance.voices[0].gain - expected).abs() < 0.01);
+}
+
+#[test]
+fn instances_only_play_notes_in_their_zone() {
+    let (mut sampler, mut draw_data) = Sampler::new(SamplerConfig::new(44100.0).instances(3));
+    sampler.set_key_range(0, 0, 59);
+    sampler.set_key_range(1, 60, 127);
+    sampler.set_velocity_range(1, 0.5, 1.0);
+    sampler.set_midi_channel_from_preset(2, 2);
+    for index in 0..3 {
+        sampler.set_mode(index, Mode::Tape);
+    }
+
+    sampler.note_on(0, 48, 1.0);
+    sampler.note_on(0, 72, 1.0);
+    sampler.note_on(0, 74, 0.2);
+    sampler.note_on(1, 76, 1.0);
+
+    let input = vec![0.0; 44100 / 10];
+    let mut output = (vec![0.0; input.len()], vec![0.0; input.len()]);
+    sampler.process_block((&input, &input), (&mut output.0, &mut output.1));
+
+    let playing: Vec<usize> = draw_data
+        .read()
+        .iter()
+        .map(|data| data.play_heads.iter().flatten().count())
+        .collect();
+    assert_eq!(playing, [1, 2, 1]);
+}
+
+#[test]
+fn pitch_bend_and_mod_wheel_retune_smoothly() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.set_mode(Mode::Tape);
+    instance.set_bend_range(12.0);
+    instance.note_on(0, 60, 1.0);
+    instance.pitch_bend(0, 1.0);
+
+    let step = |instance: &mut Instance, samples: usize| {
+        let start = instance.voices[0].play_pos;
+        for _ in 0..samples {
+            instance.render((0.0, 0.0));
+        }
+        (instance.voices[0].play_pos - start) / samples as f32
+    };
+
+    // the bend glides instead of jumping an octave at once
+    assert!(step(&mut instance, 1) < 1.1);
+    step(&mut instance, 4410);
+    assert!((step(&mut instance, 100) - 2.0).abs() < 1e-2);
+
+    instance.set_mod_wheel_target(ModTarget::Pitch);
+    instance.set_mod_wheel_amount(-1.0);
+    instance.mod_wheel(0, 1.0);
+    step(&mut instance, 4410);
+    assert!((step(&mut instance, 100) - 1.0).abs() < 1e-2);
+}
+
+#[test]
+fn pitch_bend_only_reaches_instances_on_its_channel() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.set_mode(Mode::Tape);
+    instance.set_bend_range(12.0);
+    instance.set_midi_channel(Some(3));
+    instance.note_on(3, 60, 1.0);
+
+    let step = |instance: &mut Instance, samples: usize| {
+        let start = instance.voices[0].play_pos;
+        for _ in 0..samples {
+            instance.render((0.0, 0.0));
+        }
+        (instance.voices[0].play_pos - start) / samples as f32
+    };
+
+    instance.pitch_bend(0, 1.0);
+    step(&mut instance, 4410);
+    assert!((step(&mut instance, 100) - 1.0).abs() < 1e-2);
+
+    instance.pitch_bend(3, 1.0);
+    step(&mut instance, 4410);
+    assert!((step(&mut instance, 100) - 2.0).abs() < 1e-2);
+}
+
+#[test]
+fn synced_lfos_follow_the_tempo() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.set_tempo(120.0);
+    instance.set_lfo_shape(0, LfoShape::Saw);
+    instance.set_lfo_sync(0, Some(Division::Bar));
+    instance.set_lfo_depth(0, 1.0);
+    instance.set_lfo_target(0, ModTarget::Pan);
+
+    // a bar at 120 bpm takes two seconds, so the saw is a quarter through
+    for _ in 0..22050 {
+        instance.render((0.0, 0.0));
+    }
+    assert!((instance.modulated_values().pan + 0.5).abs() < 0.01);
+    assert_eq!(instance.state.pan, 0.0);
+}
+
+#[test]
+fn lfo_modulation_is_visible_in_draw_data() {
+    let (mut sampler, mut draw_data) = Sampler::new(SamplerConfig::new(44100.0).instances(1));
+    sampler.set_density(0, 10.0);
+    sampler.set_lfo_shape(0, 1, LfoShape::Square);
+    sampler.set_lfo_rate(0, 1, 3.0);
+    sampler.set_lfo_depth(0, 1, 0.5);
+    sampler.set_lfo_target(0, 1, ModTarget::Density);
+
+    let input = vec![0.0; 2000];
+    let mut output = (vec![0.0; input.len()], vec![0.0; input.len()]);
+    let mut densities = vec![];
+    for _ in 0..20 {
+        sampler.process_block((&input, &input), (&mut output.0, &mut output.1));
+        densities.push(draw_data.read()[0].modulated.density);
+    }
+
+    // the square moves the density an octave up and down
+    assert!(densities
+        .iter()
+        .all(|d| (d - 20.0).abs() < 1e-3 || (d - 5.0).abs() < 1e-3));
+    assert!(densities.iter().any(|d| *d > 10.0) && densities.iter().any(|d| *d < 10.0));
+}
+
+#[test]
+fn grain_scheduling_spaces_onsets() {
+    let onset_intervals = |scheduling: GrainScheduling| {
+        let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+        instance.set_scheduling(scheduling);
+        instance.set_jitter(0.5);
+        instance.set_density(100.0);
+        instance.set_grain_length(0.001);
+        instance.note_on(0, 60, 1.0);
+
+        let mut intervals = vec![];
+        let (mut last_onset, mut grain_num) = (0, 0);
+        for index in 0..441000 {
+            instance.render((0.0, 0.0));
+            if instance.grain_data.len() > grain_num && index > 0 {
+                intervals.push(index - last_onset);
+                last_onset = index;
+            }
+            grain_num = instance.grain_data.len();
+        }
+        intervals
+    };
+
+    let intervals = onset_intervals(GrainScheduling::Synchronous);
+    assert!(intervals
+        .iter()
+        .all(|interval| (440..=442).contains(interval)));
+
+    // Poisson onsets keep the average density but often come in short bursts
+    let intervals = onset_intervals(GrainScheduling::Asynchronous);
+    let mean = intervals.iter().sum::<usize>() as f32 / intervals.len() as f32;
+    assert!((mean / 441.0 - 1.0).abs() < 0.15);
+    assert!(intervals.iter().filter(|interval| **interval < 220).count() > intervals.len() / 5);
+}
+
+#[test]
+fn jittered_onsets_stay_on_the_grid() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.set_scheduling(GrainScheduling::Jittered);
+    instance.set_jitter(0.25);
+    instance.set_density(100.0);
+    instance.set_grain_length(0.001);
+    instance.note_on(0, 60, 1.0);
+
+    let mut onsets = vec![];
+    let mut grain_num = 0;
+    for index in 0..441000 {
+        instance.render((0.0, 0.0));
+        if instance.grain_data.len() > grain_num {
+            onsets.push(index as f32);
+        }
+        grain_num = instance.grain_data.len();
+    }
+
+    // each onset is moved around its own grid point instead of the previous
+    // onset, so over a thousand periods they don't wander off
+    let offsets: Vec<f32> = onsets
+        .iter()
+        .enumerate()
+        .map(|(i, onset)| onset - i as f32 * 441.0)
+        .collect();
+    assert!(offsets
+        .iter()
+        .all(|offset| offset.abs() <= 0.25 * 441.0 + 2.0));
+    let mean = offsets.iter().sum::<f32>() / offsets.len() as f32;
+    assert!(mean.abs() < 10.0);
+    let spread = offsets
+        .iter()
+        .fold(0.0_f32, |max, offset| max.max(offset.abs()));
+    assert!(spread > 50.0);
+}
+
+#[test]
+fn density_loop_and_speed_follow_the_tempo() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.load_audio([vec![0.0; 441000], vec![0.0; 441000]]);
+    instance.set_tempo(120.0);
+    instance.set_loop_start(0.0);
+    instance.set_density_sync(Some(Division::Sixteenth));
+    instance.set_loop_beats(Some(4));
+    instance.set_speed_bars(Some(1));
+
+    // four beats at 120 bpm are two seconds of the ten second buffer
+    let modulated = instance.modulated_values();
+    assert!((modulated.density - 8.0).abs() < 1e-3);
+    assert!((modulated.loop_length - 0.2).abs() < 1e-3);
+
+    // the loop is traversed once per bar, so it is half way after a second
+    instance.note_on(0, 60, 1.0);
+    for _ in 0..44100 {
+        instance.render((0.0, 0.0));
+    }
+    assert!((instance.voices[0].play_pos - 44100.0).abs() < 10.0);
+
+    instance.set_tempo(60.0);
+    let modulated = instance.modulated_values();
+    assert!((modulated.density - 4.0).abs() < 1e-3);
+    assert!((modulated.loop_length - 0.4).abs() < 1e-3);
+}
+
+#[test]
+fn overdub_punches_into_the_loop_region() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.load_audio([vec![0.5; 1000], vec![0.5; 1000]]);
+    instance.set_loop_start(0.25);
+    instance.set_loop_length(0.5);
+    instance.set_overdub(true);
+    instance.set_feedback(0.5);
+    instance.set_punch(true);
+
+    instance.record();
+    assert_eq!(instance.write_index, 250);
+    for _ in 0..500 {
+        instance.render((0.5, 0.5));
+    }
+    instance.stop_recording();
+
+    // the loop region is layered once, the rest of the buffer is untouched
+    assert!(instance.buffer[0][..250].iter().all(|s| *s == 0.5));
+    assert!(instance.buffer[0][250..750].iter().all(|s| *s == 0.75));
+    assert!(instance.buffer[1][750..1000].iter().all(|s| *s == 0.5));
+
+    instance.set_feedback(0.0);
+    instance.record();
+    for _ in 0..500 {
+        instance.render((0.25, 0.25));
+    }
+    instance.stop_recording();
+    assert!(instance.buffer[0][250..750].iter().all(|s| *s == 0.25));
+    assert_eq!(instance.buffer[0][750], 0.5);
+}
+
+#[test]
+fn overdub_keeps_layering_until_it_is_stopped() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+    instance.load_audio([vec![0.0; 100], vec![0.0; 100]]);
+    instance.set_overdub(true);
+    instance.set_feedback(1.0);
+
+    instance.record();
+    for _ in 0..250 {
+        instance.render((0.1, 0.1));
+    }
+
+    // wrapped around twice and still recording
+    assert!(instance.state.is_recording);
+    assert_eq!(instance.write_index, 50);
+    assert!(instance.buffer[0][..50]
+        .iter()
+        .all(|s| (s - 0.3).abs() < 1e-6));
+    assert!(instance.buffer[1][50..100]
+        .iter()
+        .all(|s| (s - 0.2).abs() < 1e-6));
+
+    instance.stop_recording();
+    assert!(!instance.state.is_recording);
+    assert_eq!(instance.current_buffer_size, 100);
+}
+
+#[test]
+fn toggled_recording_takes_the_recorded_length() {
+    let mut instance = Instance::new(&SamplerConfig::new(44100.0));
+
+    instance.toggle_record();
+    for _ in 0..22050 {
+        instance.render((0.5, 0.5));
+    }
+    instance.toggle_record();
+
+    assert!(!instance.state.is_recording);
+    assert_eq!(instance.current_buffer_size, 22050);
+    // the half second spreads over the whole overview
+    assert!(instance
+        .buffer_to_draw
+        .buffer
+        .iter()
+        .all(|bar| (bar - 0.5).abs() < 1e-3));
+
+    // a recording that is never stopped ends with a full buffer
+    let mut instance = Instance::new(&SamplerConfig::new(1000.0).max_buffer_seconds(2.0));
+    instance.toggle_record();
+    for _ in 0..3000 {
+        instance.render((0.5, 0.5));
+    }
+    assert!(!instance.state.is_recording);
+    assert_eq!(instance.current_buffer_size, instance.max_buffer_size);
+}
+
+#[test]
+fn armed_recording_starts_at_the_threshold_with_pre_roll() {
+    let mut instance = Instance::new(&SamplerConfig::new(1000.0).max_buffer_seconds(2.0));
+    instance.set_record_threshold(0.5);
+    instance.arm();
+
+    for _ in 0..100 {
+        instance.render((0.01, 0.01));
+    }
+    assert!(instance.state.is_armed && !instance.state.is_recording);
+
+    instance.render((0.8, 0.8));
+    assert!(!instance.state.is_armed && instance.state.is_recording);
+
+    // the 50 ms pre-roll is kept in front of the transient
+    assert_eq!(instance.write_index, 51);
+    assert!(instance.buffer[0][..50].iter().all(|s| *s == 0.01));
+    assert_eq!(instance.buffer[1][50], 0.8);
+}
+
+#[test]
+fn live_mode_reads_behind_the_write_head_until_frozen() {
+    let mut instance = Instance::new(&SamplerConfig::new(1000.0).record_seconds(1.0));
+    instance.set_live(true);
+    instance.set_live_delay(0.1);
+    instance.note_on(0, 60, 1.0);
+
+    for i in 0..1300 {
+        instance.render((i as f32, i as f32));
+    }
+
+    // the ring has wrapped once and the play head trails by 100 samples
+    assert_eq!(instance.current_buffer_size, 1000);
+    assert_eq!(instance.buffer[0][299], 1299.0);
+    assert_eq!(instance.voices[0].play_pos, 200.0);
+
+    instance.set_freeze(true);
+    for _ in 0..100 {
+        instance.render((-1.0, -1.0));
+    }
+    assert_eq!(instance.buffer[0][299], 1299.0);
+    assert!(!instance.buffer[0].contains(&-1.0));
+    assert_eq!(instance.voices[0].play_pos, 200.0);
+}
+
+#[test]
+fn sample_rate_changes_leave_live_mode() {
+    let mut instance = Instance::new(&SamplerConfig::new(1000.0).record_seconds(1.0));
+    instance.set_live(true);
+    instance.note_on(0, 60, 1.0);
+    for _ in 0..500 {
+        instance.render((0.5, 0.5));
+    }
+
+    instance.set_sample_rate(2000.0);
+    assert!(!instance.state.is_live);
+
+    // the voice plays on instead of sticking to the old write head
+    let start = instance.voices[0].play_pos;
+    for _ in 0..10 {
+        instance.render((0.0, 0.0));
+    }
+    assert_ne!(instance.voices[0].play_pos, start);
+}
+
+#[test]
+fn pitch_spray_quantizes_to_scales() {
+    let major = Scale::Major.pitch_classes(0).unwrap();
+    assert_eq!(quantize(1.2, major), 2.0);
+    assert_eq!(quantize(-1.4, major), -1.0);
+    assert_eq!(quantize(5.6, major), 5.0);
+
+    let fifths = Scale::Fifths.pitch_classes(0).unwrap();
+    assert_eq!(quantize(10.0, fifths), 12.0);
+    assert_eq!(quantize(-4.0, fifths), -5.0);
+
+    // user intervals wrap around the octave
+    let custom = pitch_classes_from_intervals(&[0, 3, 19]);
+    assert_eq!(Scale::Custom.pitch_classes(custom), Some(custom));
+    assert_eq!(quantize(6.0, custom), 7.0);
+    assert_eq!(quantize(-8.0, custom), -9.0);
+
+    assert_eq!(Scale::Free.pitch_classes(custom), None);
+    assert_eq!(Scale::Custom.pitch_classes(0), None);
+}
+
+#[test]
+fn back_and_forth_turns_around_at_the_loop_ends() {
+    let (mut sampler, mut draw_data) = Sampler::new(SamplerConfig::new(1000.0).instances(1));
+    sampler.load_buf([vec![0.0; 1000], vec![0.0; 1000]], 0);
+    sampler.set_mode_from_preset(0, 1);
+    sampler.set_loop_start(0, 0.25);
+    sampler.set_loop_length(0, 0.5);
+    sampler.set_play_dir_from_preset(0, 2);
+    sampler.note_on(0, 60, 1.0);
+
+    let input = vec![0.0; 30];
+    let mut output = (vec![0.0; 30], vec![0.0; 30]);
+    let mut positions = vec![];
+    for _ in 0..60 {
+        sampler.process_block((&input, &input), (&mut output.0, &mut output.1));
+        positions.push(draw_data.read()[0].play_heads[0].unwrap());
+    }
+
+    // the head moves steadily up to the loop end and back down again
+    assert!(positions.iter().all(|pos| (250.0..=750.0).contains(pos)));
+    assert!(positions.windows(2).all(|w| (w[1] - w[0]).abs() <= 30.0));
+    let turn = positions.windows(2).position(|w| w[1] < w[0]).unwrap();
+    assert!(positions[turn] > 720.0);
+    assert!(positions.iter().skip(turn).any(|pos| *pos < 280.0));
+}
+
+#[test]
+fn reverse_probability_mixes_grain_directions() {
+    let grain_directions = |probability: f32| {
+        let mut instance = Instance::new(&SamplerConfig::new(1000.0));
+        instance.set_density(100.0);
+        instance.set_spray(0.0);
+        instance.set_grain_length(0.5);
+        instance.set_reverse_probability(probability);
+        instance.note_on(0, 60, 1.0);
+
+        // no grain has ended yet, so every grain keeps its slot
+        for _ in 0..400 {
+            instance.render((0.0, 0.0));
+        }
+        let before: Vec<f32> = instance.grain_data.iter().map(|grain| grain.pos).collect();
+        instance.render((0.0, 0.0));
+        let steps = before
+            .iter()
+            .zip(instance.grain_data.iter())
+            .map(|(pos, grain)| grain.pos - pos);
+
+        steps.fold((0, 0), |(forward, backward), step| {
+            if step > 0.0 {
+                (forward + 1, backward)
+            } else {
+                (forward, backward + 1)
+            }
+        })
+    };
+
+    let (forward, backward) = grain_directions(0.0);
+    assert!(forward > 30 && backward == 0);
+    let (forward, backward) = grain_directions(1.0);
+    assert!(forward == 0 && backward > 30);
+    let (forward, backward) = grain_directions(0.5);
+    assert!(forward > 0 && backward > 0);
+}
+
+#[test]
+fn grain_filters_shape_the_spectrum_per_grain() {
+    let level = |mode: FilterMode, input: &dyn Fn(usize) -> f32| {
+        let mut filter = Svf::new(mode, 1000.0, 0.5, 48000.0);
+        (0..4800)
+            .map(|i| filter.process((input(i), input(i))).0)
+            .skip(2400)
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+    };
+    let nyquist = |i: usize| if i.is_multiple_of(2) { 1.0 } else { -1.0 };
+    let dc = |_: usize| 1.0;
+
+    assert!(level(FilterMode::LowPass, &nyquist) < 0.01);
+    assert!(level(FilterMode::LowPass, &dc) > 0.99);
+    assert!(level(FilterMode::HighPass, &dc) < 0.01);
+    assert!(level(FilterMode::HighPass, &nyquist) > 0.99);
+    assert!(level(FilterMode::BandPass, &dc) < 0.01);
+    assert_eq!(level(FilterMode::Off, &nyquist), 1.0);
+
+    let grain_level = |mode: FilterMode| {
+        let mut instance = Instance::new(&SamplerConfig::new(48000.0));
+        let audio: Vec<f32> = (0..48000).map(nyquist).collect();
+        instance.load_audio([audio.clone(), audio]);
+        instance.set_filter_mode(mode);
+        instance.set_cutoff(200.0);
+        instance.set_cutoff_spray(2.0);
+        instance.set_resonance_spray(0.5);
+        instance.note_on(0, 60, 1.0);
+
+        (0..9600)
+            .map(|_| instance.render((0.0, 0.0)).0.abs())
+            .sum::<f32>()
+    };
+
+    // even with the cutoff scattered by two octaves, no grain opens up to
+    // the top of the spectrum
+    assert!(grain_level(FilterMode::LowPass) < grain_level(FilterMode::Off) * 0.05);
+}
+
+#[test]
+fn fx_chain_drives_delays_and_reverbs() {
+    let impulse = |fx: &mut FxChain| -> Vec<f32> {
+        (0..2000)
+            .map(|i| fx.process(if i == 0 { (1.0, 1.0) } else { (0.0, 0.0) }).0)
+            .collect()
+    };
+
+    // everything off passes the input through
+    let mut fx = FxChain::new(1000.0);
+    assert_eq!(fx.process((0.25, -0.5)), (0.25, -0.5));
+
+    fx.set_drive(1.0);
+    let (small, full) = (fx.process((0.1, 0.1)).0, fx.process((1.0, 1.0)).0);
+    assert!(small > 0.9 && (full - 1.0).abs() < 1e-6);
+
+    let mut fx = FxChain::new(1000.0);
+    fx.set_delay_time(0.1);
+    fx.set_delay_feedback(0.5);
+    fx.set_delay_mix(1.0);
+    // lets the delay time glide over from its default
+    for _ in 0..1000 {
+        fx.process((0.0, 0.0));
+    }
+    let output = impulse(&mut fx);
+    assert_eq!(output[0], 0.0);
+    assert!((output[100] - 1.0).abs() < 1e-6);
+    assert!((output[200] - 0.5).abs() < 1e-6);
+
+    let mut fx = FxChain::new(48000.0);
+    fx.set_reverb_mix(1.0);
+    let output = impulse(&mut fx);
+    assert!(output[1500..].iter().any(|sample| sample.abs() > 1e-4));
+}
+
+#[test]
+fn fx_mixes_glide_and_faded_out_stages_are_cleared() {
+    let mut fx = FxChain::new(1000.0);
+    fx.set_delay_time(0.1);
+    fx.set_delay_feedback(0.9);
+    fx.set_delay_mix(1.0);
+
+    // the mix glides in instead of cutting the dry signal at once
+    assert!(fx.process((1.0, 1.0)).0 > 0.9);
+    for _ in 0..500 {
+        fx.process((1.0, 1.0));
+    }
+
+    fx.set_delay_mix(0.0);
+    for _ in 0..1000 {
+        fx.process((0.0, 0.0));
+    }
+    assert!(fx.is_neutral());
+    assert_eq!(fx.process((0.5, -0.5)), (0.5, -0.5));
+
+    // the repeats from before the fade out do not come back
+    fx.set_delay_mix(1.0);
+    assert!((0..500).all(|_| fx.process((0.0, 0.0)) == (0.0, 0.0)));
+
+    let mut fx = FxChain::new(1000.0);
+    fx.set_reverb_size(1.0);
+    fx.set_reverb_mix(1.0);
+    fx.process((1.0, 1.0));
+    for _ in 0..200 {
+        fx.process((0.0, 0.0));
+    }
+    fx.set_reverb_mix(0.0);
+    for _ in 0..1000 {
+        fx.process((0.0, 0.0));
+    }
+    assert!(fx.is_neutral());
+    fx.set_reverb_mix(1.0);
+    assert!((0..500).all(|_| fx.process((0.0, 0.0)) == (0.0, 0.0)));
+}
@@ -1,3 +1,4 @@
+use crossbeam::atomic::AtomicCell;
 use crossbeam::channel::Sender;
 use nih_plug::nih_error;
 use nih_plug::prelude::Editor;
@@ -10,7 +11,7 @@ use crate::editor::widgets::dial::Dial;
 use crate::editor::widgets::select::Select;
 use crate::editor::widgets::waveform::Waveform;
 use crate::{utils, FileMessage, GrainiacParams};
-use grainiac_core::{DrawData, Output};
+use grainiac_core::{DrawData, Output, LFO_NUM};
 
 mod widgets;
 
@@ -18,6 +19,7 @@ mod widgets;
 struct Data {
     params: Arc<GrainiacParams>,
     sender: Arc<Sender<FileMessage>>,
+    sample_rate: Arc<AtomicCell<f32>>,
 }
 
 impl Data {
@@ -29,7 +31,7 @@ impl Data {
 
         if let Some(path) = file {
             let path_str = path.to_string_lossy().to_string();
-            if let Some(samples) = utils::AudioHandler::open(path) {
+            if let Some(samples) = utils::AudioHandler::open(path, self.sample_rate.load()) {
                 if let Ok(mut paths) = self.params.audio_paths.lock() {
                     paths[index] = Some(path_str);
                 }
@@ -53,7 +55,7 @@ impl Model for Data {
 }
 
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (800, 900))
+    ViziaState::new(|| (800, 1850))
 }
 
 pub(crate) fn create(
@@ -61,6 +63,7 @@ pub(crate) fn create(
     editor_state: Arc<ViziaState>,
     draw_data: Arc<Mutex<Output<Vec<DrawData>>>>,
     sender: Arc<Sender<FileMessage>>,
+    sample_rate: Arc<AtomicCell<f32>>,
 ) -> Option<Box<dyn Editor>> {
     create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
         if let Err(err) = cx.add_stylesheet(include_style!("src/editor/styles.css")) {
@@ -73,6 +76,7 @@ pub(crate) fn create(
         Data {
             params: params.clone(),
             sender: sender.clone(),
+            sample_rate: sample_rate.clone(),
         }
         .build(cx);
 
@@ -136,14 +140,14 @@ fn waveform(cx: &mut Context, draw_data: Arc<Mutex<Output<Vec<DrawData>>>>, inde
 
 fn instance(cx: &mut Context, index: usize) {
     HStack::new(cx, |cx| {
-        Select::new(cx, "grain dir", 2, Data::params, move |params| {
+        Select::new(cx, "grain dir", 3, Data::params, move |params| {
             &params.instances[index].g_dir
         })
         .width(Pixels(160.0))
         .left(Pixels(15.0))
         .right(Pixels(15.0));
 
-        Select::new(cx, "play dir", 2, Data::params, move |params| {
+        Select::new(cx, "play dir", 3, Data::params, move |params| {
             &params.instances[index].p_dir
         })
         .width(Pixels(160.0))
@@ -152,7 +156,69 @@ fn instance(cx: &mut Context, index: usize) {
         Select::new(cx, "Hold", 2, Data::params, move |params| {
             &params.instances[index].hold
         })
-        .width(Pixels(130.0));
+        .width(Pixels(130.0))
+        .right(Pixels(15.0));
+
+        Select::new(cx, "steal", 5, Data::params, move |params| {
+            &params.instances[index].voice_stealing
+        })
+        .width(Pixels(190.0));
+    })
+    .height(Pixels(40.0))
+    .bottom(Pixels(10.0));
+
+    HStack::new(cx, |cx| {
+        Select::new(cx, "mode", 3, Data::params, move |params| {
+            &params.instances[index].mode
+        })
+        .width(Pixels(160.0))
+        .left(Pixels(15.0))
+        .right(Pixels(15.0));
+
+        Select::new(cx, "interp", 3, Data::params, move |params| {
+            &params.instances[index].interpolation
+        })
+        .width(Pixels(190.0))
+        .right(Pixels(15.0));
+
+        Select::new(cx, "window", 7, Data::params, move |params| {
+            &params.instances[index].window
+        })
+        .width(Pixels(190.0))
+        .right(Pixels(15.0));
+
+        Select::new(cx, "vel", 4, Data::params, move |params| {
+            &params.instances[index].velocity_curve
+        })
+        .width(Pixels(160.0));
+    })
+    .height(Pixels(40.0))
+    .bottom(Pixels(10.0));
+
+    HStack::new(cx, |cx| {
+        Select::new(cx, "mod", 10, Data::params, move |params| {
+            &params.instances[index].mod_wheel_target
+        })
+        .width(Pixels(190.0))
+        .left(Pixels(15.0))
+        .right(Pixels(15.0));
+
+        Select::new(cx, "sched", 3, Data::params, move |params| {
+            &params.instances[index].scheduling
+        })
+        .width(Pixels(190.0))
+        .right(Pixels(15.0));
+
+        Select::new(cx, "scale", 8, Data::params, move |params| {
+            &params.instances[index].scale
+        })
+        .width(Pixels(190.0))
+        .right(Pixels(15.0));
+
+        Select::new(cx, "filter", 4, Data::params, move |params| {
+            &params.instances[index].filter_mode
+        })
+        .width(Pixels(160.0));
     })
     .height(Pixels(40.0))
     .bottom(Pixels(10.0));
@@ -211,8 +277,202 @@ fn instance(cx: &mut Context, index: usize) {
                 &params.instances[index].gain
             });
         });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "flat", Data::params, move |params| {
+                &params.instances[index].window_flatness
+            });
+            Dial::new(cx, "skew", Data::params, move |params| {
+                &params.instances[index].window_skew
+            });
+        });
+    })
+    .text_align(TextAlign::Center)
+    .left(Pixels(15.0))
+    .right(Pixels(15.0));
+
+    HStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "vel dens", Data::params, move |params| {
+                &params.instances[index].velocity_to_density
+            });
+            Dial::new(cx, "vel spray", Data::params, move |params| {
+                &params.instances[index].velocity_to_spray
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "vel att", Data::params, move |params| {
+                &params.instances[index].velocity_to_attack
+            });
+            Dial::new(cx, "chan", Data::params, move |params| {
+                &params.instances[index].midi_channel
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "key lo", Data::params, move |params| {
+                &params.instances[index].key_low
+            });
+            Dial::new(cx, "key hi", Data::params, move |params| {
+                &params.instances[index].key_high
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "vel lo", Data::params, move |params| {
+                &params.instances[index].velocity_low
+            });
+            Dial::new(cx, "vel hi", Data::params, move |params| {
+                &params.instances[index].velocity_high
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "bend", Data::params, move |params| {
+                &params.instances[index].bend_range
+            });
+            Dial::new(cx, "mod amt", Data::params, move |params| {
+                &params.instances[index].mod_wheel_amount
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "jitter", Data::params, move |params| {
+                &params.instances[index].jitter
+            });
+            Dial::new(cx, "dens sync", Data::params, move |params| {
+                &params.instances[index].density_sync
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "beats", Data::params, move |params| {
+                &params.instances[index].loop_beats
+            });
+            Dial::new(cx, "bars", Data::params, move |params| {
+                &params.instances[index].speed_bars
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "p spray", Data::params, move |params| {
+                &params.instances[index].pitch_spray
+            });
+            Dial::new(cx, "custom", Data::params, move |params| {
+                &params.instances[index].custom_scale
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "reverse", Data::params, move |params| {
+                &params.instances[index].reverse_probability
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "cutoff", Data::params, move |params| {
+                &params.instances[index].cutoff
+            });
+            Dial::new(cx, "res", Data::params, move |params| {
+                &params.instances[index].resonance
+            });
+        });
+
+        VStack::new(cx, |cx| {
+            Dial::new(cx, "c spray", Data::params, move |params| {
+                &params.instances[index].cutoff_spray
+            });
+            Dial::new(cx, "r spray", Data::params, move |params| {
+                &params.instances[index].resonance_spray
+            });
+        });
     })
     .text_align(TextAlign::Center)
     .left(Pixels(15.0))
     .right(Pixels(15.0));
+
+    HStack::new(cx, |cx| {
+        (0..LFO_NUM).for_each(|lfo| lfo_dials(cx, index, lfo));
+    })
+    .text_align(TextAlign::Center)
+    .left(Pixels(15.0))
+    .right(Pixels(15.0));
+
+    HStack::new(cx, |cx| fx_dials(cx, index))
+        .text_align(TextAlign::Center)
+        .left(Pixels(15.0))
+        .right(Pixels(15.0));
+}
+
+fn fx_dials(cx: &mut Context, index: usize) {
+    VStack::new(cx, |cx| {
+        Dial::new(cx, "fx filter", Data::params, move |params| {
+            &params.instances[index].fx_filter_mode
+        });
+        Dial::new(cx, "drive", Data::params, move |params| {
+            &params.instances[index].drive
+        });
+    });
+
+    VStack::new(cx, |cx| {
+        Dial::new(cx, "fx cutoff", Data::params, move |params| {
+            &params.instances[index].fx_cutoff
+        });
+        Dial::new(cx, "fx res", Data::params, move |params| {
+            &params.instances[index].fx_resonance
+        });
+    });
+
+    VStack::new(cx, |cx| {
+        Dial::new(cx, "delay", Data::params, move |params| {
+            &params.instances[index].delay_time
+        });
+        Dial::new(cx, "dl sync", Data::params, move |params| {
+            &params.instances[index].delay_sync
+        });
+    });
+
+    VStack::new(cx, |cx| {
+        Dial::new(cx, "dl fb", Data::params, move |params| {
+            &params.instances[index].delay_feedback
+        });
+        Dial::new(cx, "dl mix", Data::params, move |params| {
+            &params.instances[index].delay_mix
+        });
+    });
+
+    VStack::new(cx, |cx| {
+        Dial::new(cx, "rv size", Data::params, move |params| {
+            &params.instances[index].reverb_size
+        });
+        Dial::new(cx, "rv mix", Data::params, move |params| {
+            &params.instances[index].reverb_mix
+        });
+    });
+}
+
+fn lfo_dials(cx: &mut Context, index: usize, lfo: usize) {
+    const LABELS: [&str; LFO_NUM] = ["lfo 1", "lfo 2", "lfo 3", "lfo 4"];
+
+    VStack::new(cx, |cx| {
+        Dial::new(cx, LABELS[lfo], Data::params, move |params| {
+            &params.instances[index].lfos[lfo].shape
+        });
+        Dial::new(cx, "target", Data::params, move |params| {
+            &params.instances[index].lfos[lfo].target
+        });
+        Dial::new(cx, "sync", Data::params, move |params| {
+            &params.instances[index].lfos[lfo].sync
+        });
+    });
+
+    VStack::new(cx, |cx| {
+        Dial::new(cx, "rate", Data::params, move |params| {
+            &params.instances[index].lfos[lfo].rate
+        });
+        Dial::new(cx, "depth", Data::params, move |params| {
+            &params.instances[index].lfos[lfo].depth
+        });
+    });
 }
@@ -1,3 +1,4 @@
+use crossbeam::atomic::AtomicCell;
 use crossbeam::channel::{bounded, Receiver, Sender};
 use grainiac_core::*;
 use nih_plug::prelude::*;
@@ -8,16 +9,21 @@ use std::sync::{Arc, Mutex};
 mod editor;
 mod utils;
 
+const MPE_TIMBRE_CC: u8 = 74;
+const MOD_WHEEL_CC: u8 = 1;
+
 pub struct Grainiac {
     params: Arc<GrainiacParams>,
     sampler: Sampler,
     buf_output: Arc<Mutex<Output<Vec<DrawData>>>>,
     sender: Arc<Sender<FileMessage>>,
     receiver: Receiver<FileMessage>,
+    input_buffers: [Vec<f32>; 2],
+    sample_rate: Arc<AtomicCell<f32>>,
 }
 
 pub enum FileMessage {
-    LoadAudio(Vec<f32>, usize),
+    LoadAudio([Vec<f32>; 2], usize),
     OpenFileDialog(usize),
 }
 
@@ -25,6 +31,7 @@ pub enum FileMessage {
 pub enum PlayDirection {
     Forward,
     Backward,
+    BackAndForth,
 }
 
 impl Enum for PlayDirection {
@@ -32,23 +39,26 @@ impl Enum for PlayDirection {
         match self {
             PlayDirection::Forward => 0,
             PlayDirection::Backward => 1,
+            PlayDirection::BackAndForth => 2,
         }
     }
 
     fn from_index(index: usize) -> Self {
         if index == 0 {
             PlayDirection::Forward
-        } else {
+        } else if index == 1 {
             PlayDirection::Backward
+        } else {
+            PlayDirection::BackAndForth
         }
     }
 
     fn ids() -> Option<&'static [&'static str]> {
-        Some(&["forward", "backward"])
+        Some(&["forward", "backward", "back_and_forth"])
     }
 
     fn variants() -> &'static [&'static str] {
-        &["", ""]
+        &["", "", ""]
     }
 }
 
@@ -83,6 +93,574 @@ impl Enum for Hold {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Hermite,
+    Sinc,
+}
+
+impl Enum for Interpolation {
+    fn to_index(self) -> usize {
+        match self {
+            Interpolation::Linear => 0,
+            Interpolation::Hermite => 1,
+            Interpolation::Sinc => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        if index == 0 {
+            Interpolation::Linear
+        } else if index == 1 {
+            Interpolation::Hermite
+        } else {
+            Interpolation::Sinc
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&["linear", "hermite", "sinc"])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &["Linear", "Hermite", "Sinc"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowShape {
+    Sine,
+    Hann,
+    Gaussian,
+    Tukey,
+    Trapezoid,
+    Exponential,
+    ReverseExponential,
+}
+
+impl Enum for WindowShape {
+    fn to_index(self) -> usize {
+        match self {
+            WindowShape::Sine => 0,
+            WindowShape::Hann => 1,
+            WindowShape::Gaussian => 2,
+            WindowShape::Tukey => 3,
+            WindowShape::Trapezoid => 4,
+            WindowShape::Exponential => 5,
+            WindowShape::ReverseExponential => 6,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => WindowShape::Sine,
+            1 => WindowShape::Hann,
+            2 => WindowShape::Gaussian,
+            3 => WindowShape::Tukey,
+            4 => WindowShape::Trapezoid,
+            5 => WindowShape::Exponential,
+            _ => WindowShape::ReverseExponential,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "sine",
+            "hann",
+            "gaussian",
+            "tukey",
+            "trapezoid",
+            "exponential",
+            "reverse_exponential",
+        ])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Sine",
+            "Hann",
+            "Gaussian",
+            "Tukey",
+            "Trapezoid",
+            "Exp",
+            "Rev Exp",
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    Grain,
+    Tape,
+    Stretch,
+}
+
+impl Enum for PlayMode {
+    fn to_index(self) -> usize {
+        match self {
+            PlayMode::Grain => 0,
+            PlayMode::Tape => 1,
+            PlayMode::Stretch => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => PlayMode::Grain,
+            1 => PlayMode::Tape,
+            _ => PlayMode::Stretch,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&["grain", "tape", "stretch"])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &["Grain", "Tape", "Stretch"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceStealing {
+    Oldest,
+    Quietest,
+    SameNote,
+    LowestPriority,
+    HighestPriority,
+}
+
+impl Enum for VoiceStealing {
+    fn to_index(self) -> usize {
+        match self {
+            VoiceStealing::Oldest => 0,
+            VoiceStealing::Quietest => 1,
+            VoiceStealing::SameNote => 2,
+            VoiceStealing::LowestPriority => 3,
+            VoiceStealing::HighestPriority => 4,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => VoiceStealing::Oldest,
+            1 => VoiceStealing::Quietest,
+            2 => VoiceStealing::SameNote,
+            3 => VoiceStealing::LowestPriority,
+            _ => VoiceStealing::HighestPriority,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&["oldest", "quietest", "same_note", "lowest", "highest"])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &["Oldest", "Quietest", "Same Note", "Low Prio", "High Prio"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityCurve {
+    Linear,
+    Soft,
+    Hard,
+    Fixed,
+}
+
+impl Enum for VelocityCurve {
+    fn to_index(self) -> usize {
+        match self {
+            VelocityCurve::Linear => 0,
+            VelocityCurve::Soft => 1,
+            VelocityCurve::Hard => 2,
+            VelocityCurve::Fixed => 3,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => VelocityCurve::Linear,
+            1 => VelocityCurve::Soft,
+            2 => VelocityCurve::Hard,
+            _ => VelocityCurve::Fixed,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&["linear", "soft", "hard", "fixed"])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &["Linear", "Soft", "Hard", "Fixed"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModTarget {
+    Off,
+    LoopStart,
+    LoopLength,
+    Density,
+    GrainLength,
+    Spray,
+    Pan,
+    Spread,
+    Pitch,
+    Gain,
+}
+
+impl Enum for ModTarget {
+    fn to_index(self) -> usize {
+        match self {
+            ModTarget::Off => 0,
+            ModTarget::LoopStart => 1,
+            ModTarget::LoopLength => 2,
+            ModTarget::Density => 3,
+            ModTarget::GrainLength => 4,
+            ModTarget::Spray => 5,
+            ModTarget::Pan => 6,
+            ModTarget::Spread => 7,
+            ModTarget::Pitch => 8,
+            ModTarget::Gain => 9,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => ModTarget::Off,
+            1 => ModTarget::LoopStart,
+            2 => ModTarget::LoopLength,
+            3 => ModTarget::Density,
+            4 => ModTarget::GrainLength,
+            5 => ModTarget::Spray,
+            6 => ModTarget::Pan,
+            7 => ModTarget::Spread,
+            8 => ModTarget::Pitch,
+            _ => ModTarget::Gain,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "off",
+            "loop_start",
+            "loop_length",
+            "density",
+            "grain_length",
+            "spray",
+            "pan",
+            "spread",
+            "pitch",
+            "gain",
+        ])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Off",
+            "Loop Start",
+            "Loop Length",
+            "Density",
+            "Grain Length",
+            "Spray",
+            "Pan",
+            "Spread",
+            "Pitch",
+            "Gain",
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    SampleAndHold,
+    SmoothRandom,
+}
+
+impl Enum for LfoShape {
+    fn to_index(self) -> usize {
+        match self {
+            LfoShape::Sine => 0,
+            LfoShape::Triangle => 1,
+            LfoShape::Saw => 2,
+            LfoShape::Square => 3,
+            LfoShape::SampleAndHold => 4,
+            LfoShape::SmoothRandom => 5,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => LfoShape::Sine,
+            1 => LfoShape::Triangle,
+            2 => LfoShape::Saw,
+            3 => LfoShape::Square,
+            4 => LfoShape::SampleAndHold,
+            _ => LfoShape::SmoothRandom,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "sine",
+            "triangle",
+            "saw",
+            "square",
+            "sample_and_hold",
+            "smooth_random",
+        ])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &["Sine", "Triangle", "Saw", "Square", "S&H", "Random"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempoSync {
+    Free,
+    FourBars,
+    TwoBars,
+    Bar,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+impl Enum for TempoSync {
+    fn to_index(self) -> usize {
+        match self {
+            TempoSync::Free => 0,
+            TempoSync::FourBars => 1,
+            TempoSync::TwoBars => 2,
+            TempoSync::Bar => 3,
+            TempoSync::Half => 4,
+            TempoSync::Quarter => 5,
+            TempoSync::Eighth => 6,
+            TempoSync::Sixteenth => 7,
+            TempoSync::ThirtySecond => 8,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => TempoSync::Free,
+            1 => TempoSync::FourBars,
+            2 => TempoSync::TwoBars,
+            3 => TempoSync::Bar,
+            4 => TempoSync::Half,
+            5 => TempoSync::Quarter,
+            6 => TempoSync::Eighth,
+            7 => TempoSync::Sixteenth,
+            _ => TempoSync::ThirtySecond,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "free",
+            "4_bars",
+            "2_bars",
+            "bar",
+            "half",
+            "quarter",
+            "eighth",
+            "sixteenth",
+            "thirty_second",
+        ])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Free", "4 Bars", "2 Bars", "1/1", "1/2", "1/4", "1/8", "1/16", "1/32",
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrainScheduling {
+    Synchronous,
+    Asynchronous,
+    Jittered,
+}
+
+impl Enum for GrainScheduling {
+    fn to_index(self) -> usize {
+        match self {
+            GrainScheduling::Synchronous => 0,
+            GrainScheduling::Asynchronous => 1,
+            GrainScheduling::Jittered => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => GrainScheduling::Synchronous,
+            1 => GrainScheduling::Asynchronous,
+            _ => GrainScheduling::Jittered,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&["synchronous", "asynchronous", "jittered"])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &["Sync", "Async", "Jitter"]
+    }
+}
+
+#[derive(Params)]
+struct LfoParams {
+    #[id = "lfo_shape"]
+    pub shape: EnumParam<LfoShape>,
+    #[id = "lfo_target"]
+    pub target: EnumParam<ModTarget>,
+    #[id = "lfo_rate"]
+    pub rate: FloatParam,
+    #[id = "lfo_depth"]
+    pub depth: FloatParam,
+    #[id = "lfo_sync"]
+    pub sync: EnumParam<TempoSync>,
+}
+
+impl LfoParams {
+    fn new() -> Self {
+        LfoParams {
+            shape: EnumParam::new("LFO Shape", LfoShape::Sine),
+
+            target: EnumParam::new("LFO Target", ModTarget::Off),
+
+            rate: FloatParam::new(
+                "LFO Rate",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_unit(" Hz"),
+
+            depth: FloatParam::new(
+                "LFO Depth",
+                0.5,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            sync: EnumParam::new("LFO Sync", TempoSync::Free),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    Free,
+    Chromatic,
+    Major,
+    Minor,
+    Pentatonic,
+    WholeTone,
+    Fifths,
+    Custom,
+}
+
+impl Enum for Scale {
+    fn to_index(self) -> usize {
+        match self {
+            Scale::Free => 0,
+            Scale::Chromatic => 1,
+            Scale::Major => 2,
+            Scale::Minor => 3,
+            Scale::Pentatonic => 4,
+            Scale::WholeTone => 5,
+            Scale::Fifths => 6,
+            Scale::Custom => 7,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => Scale::Free,
+            1 => Scale::Chromatic,
+            2 => Scale::Major,
+            3 => Scale::Minor,
+            4 => Scale::Pentatonic,
+            5 => Scale::WholeTone,
+            6 => Scale::Fifths,
+            _ => Scale::Custom,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&[
+            "free",
+            "chromatic",
+            "major",
+            "minor",
+            "pentatonic",
+            "whole_tone",
+            "fifths",
+            "custom",
+        ])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &[
+            "Free", "Chrom", "Major", "Minor", "Penta", "Whole", "Fifths", "Custom",
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Off,
+    LowPass,
+    BandPass,
+    HighPass,
+}
+
+impl Enum for FilterMode {
+    fn to_index(self) -> usize {
+        match self {
+            FilterMode::Off => 0,
+            FilterMode::LowPass => 1,
+            FilterMode::BandPass => 2,
+            FilterMode::HighPass => 3,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => FilterMode::Off,
+            1 => FilterMode::LowPass,
+            2 => FilterMode::BandPass,
+            _ => FilterMode::HighPass,
+        }
+    }
+
+    fn ids() -> Option<&'static [&'static str]> {
+        Some(&["off", "low_pass", "band_pass", "high_pass"])
+    }
+
+    fn variants() -> &'static [&'static str] {
+        &["Off", "LP", "BP", "HP"]
+    }
+}
+
 #[derive(Params)]
 struct InstanceParams {
     #[id = "loop_start"]
@@ -115,6 +693,92 @@ struct InstanceParams {
     pub p_dir: EnumParam<PlayDirection>,
     #[id = "hold"]
     pub hold: EnumParam<Hold>,
+    #[id = "mode"]
+    pub mode: EnumParam<PlayMode>,
+    #[id = "interpolation"]
+    pub interpolation: EnumParam<Interpolation>,
+    #[id = "window"]
+    pub window: EnumParam<WindowShape>,
+    #[id = "window_flatness"]
+    pub window_flatness: FloatParam,
+    #[id = "window_skew"]
+    pub window_skew: FloatParam,
+    #[id = "voice_stealing"]
+    pub voice_stealing: EnumParam<VoiceStealing>,
+    #[id = "velocity_curve"]
+    pub velocity_curve: EnumParam<VelocityCurve>,
+    #[id = "velocity_to_density"]
+    pub velocity_to_density: FloatParam,
+    #[id = "velocity_to_spray"]
+    pub velocity_to_spray: FloatParam,
+    #[id = "velocity_to_attack"]
+    pub velocity_to_attack: FloatParam,
+    #[id = "key_low"]
+    pub key_low: IntParam,
+    #[id = "key_high"]
+    pub key_high: IntParam,
+    #[id = "velocity_low"]
+    pub velocity_low: IntParam,
+    #[id = "velocity_high"]
+    pub velocity_high: IntParam,
+    #[id = "midi_channel"]
+    pub midi_channel: IntParam,
+    #[id = "bend_range"]
+    pub bend_range: IntParam,
+    #[id = "mod_wheel_target"]
+    pub mod_wheel_target: EnumParam<ModTarget>,
+    #[id = "mod_wheel_amount"]
+    pub mod_wheel_amount: FloatParam,
+    #[nested(array, group = "lfos")]
+    pub lfos: [LfoParams; LFO_NUM],
+    #[id = "scheduling"]
+    pub scheduling: EnumParam<GrainScheduling>,
+    #[id = "jitter"]
+    pub jitter: FloatParam,
+    #[id = "density_sync"]
+    pub density_sync: EnumParam<TempoSync>,
+    #[id = "loop_beats"]
+    pub loop_beats: IntParam,
+    #[id = "speed_bars"]
+    pub speed_bars: IntParam,
+    #[id = "pitch_spray"]
+    pub pitch_spray: FloatParam,
+    #[id = "scale"]
+    pub scale: EnumParam<Scale>,
+    #[id = "custom_scale"]
+    pub custom_scale: IntParam,
+    #[id = "reverse_probability"]
+    pub reverse_probability: FloatParam,
+    #[id = "filter_mode"]
+    pub filter_mode: EnumParam<FilterMode>,
+    #[id = "cutoff"]
+    pub cutoff: FloatParam,
+    #[id = "resonance"]
+    pub resonance: FloatParam,
+    #[id = "cutoff_spray"]
+    pub cutoff_spray: FloatParam,
+    #[id = "resonance_spray"]
+    pub resonance_spray: FloatParam,
+    #[id = "fx_filter_mode"]
+    pub fx_filter_mode: EnumParam<FilterMode>,
+    #[id = "fx_cutoff"]
+    pub fx_cutoff: FloatParam,
+    #[id = "fx_resonance"]
+    pub fx_resonance: FloatParam,
+    #[id = "drive"]
+    pub drive: FloatParam,
+    #[id = "delay_time"]
+    pub delay_time: FloatParam,
+    #[id = "delay_sync"]
+    pub delay_sync: EnumParam<TempoSync>,
+    #[id = "delay_feedback"]
+    pub delay_feedback: FloatParam,
+    #[id = "delay_mix"]
+    pub delay_mix: FloatParam,
+    #[id = "reverb_size"]
+    pub reverb_size: FloatParam,
+    #[id = "reverb_mix"]
+    pub reverb_mix: FloatParam,
 }
 
 impl InstanceParams {
@@ -194,6 +858,240 @@ impl InstanceParams {
             p_dir: EnumParam::new("Grain Direction", PlayDirection::Forward),
 
             hold: EnumParam::new("Hold", Hold::Off),
+
+            mode: EnumParam::new("Mode", PlayMode::Grain),
+
+            interpolation: EnumParam::new("Interpolation", Interpolation::Linear),
+
+            window: EnumParam::new("Window", WindowShape::Sine),
+
+            window_flatness: FloatParam::new(
+                "Window Flatness",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            window_skew: FloatParam::new(
+                "Window Skew",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            voice_stealing: EnumParam::new("Voice Stealing", VoiceStealing::Oldest),
+
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+
+            velocity_to_density: FloatParam::new(
+                "Velocity to Density",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            velocity_to_spray: FloatParam::new(
+                "Velocity to Spray",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            velocity_to_attack: FloatParam::new(
+                "Velocity to Attack",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            key_low: IntParam::new("Key Low", 0, IntRange::Linear { min: 0, max: 127 }),
+
+            key_high: IntParam::new("Key High", 127, IntRange::Linear { min: 0, max: 127 }),
+
+            velocity_low: IntParam::new("Velocity Low", 0, IntRange::Linear { min: 0, max: 127 }),
+
+            velocity_high: IntParam::new(
+                "Velocity High",
+                127,
+                IntRange::Linear { min: 0, max: 127 },
+            ),
+
+            midi_channel: IntParam::new("MIDI Channel", 0, IntRange::Linear { min: 0, max: 16 })
+                .with_value_to_string(Arc::new(|value| match value {
+                    0 => String::from("Omni"),
+                    channel => channel.to_string(),
+                })),
+
+            bend_range: IntParam::new("Bend Range", 2, IntRange::Linear { min: 0, max: 24 })
+                .with_unit(" st"),
+
+            mod_wheel_target: EnumParam::new("Mod Wheel Target", ModTarget::Off),
+
+            mod_wheel_amount: FloatParam::new(
+                "Mod Wheel Amount",
+                1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            lfos: [(); LFO_NUM].map(|_| LfoParams::new()),
+
+            scheduling: EnumParam::new("Grain Scheduling", GrainScheduling::Synchronous),
+
+            jitter: FloatParam::new("Jitter", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            density_sync: EnumParam::new("Density Sync", TempoSync::Free),
+
+            loop_beats: IntParam::new("Loop Beats", 0, IntRange::Linear { min: 0, max: 32 })
+                .with_value_to_string(Arc::new(|value| match value {
+                    0 => String::from("Free"),
+                    beats => beats.to_string(),
+                })),
+
+            speed_bars: IntParam::new("Speed Bars", 0, IntRange::Linear { min: 0, max: 16 })
+                .with_value_to_string(Arc::new(|value| match value {
+                    0 => String::from("Free"),
+                    bars => bars.to_string(),
+                })),
+
+            pitch_spray: FloatParam::new(
+                "Pitch Spray",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" st")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            scale: EnumParam::new("Scale", Scale::Free),
+
+            // the pitch classes of the custom scale as bits, the lowest being the note itself
+            custom_scale: IntParam::new(
+                "Custom Scale",
+                0b0000_1001_0001,
+                IntRange::Linear {
+                    min: 1,
+                    max: 0b1111_1111_1111,
+                },
+            )
+            .with_value_to_string(Arc::new(|value| {
+                (0..12)
+                    .filter(|interval| value & 1 << interval != 0)
+                    .map(|interval| interval.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })),
+
+            reverse_probability: FloatParam::new(
+                "Reverse Probability",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            filter_mode: EnumParam::new("Filter", FilterMode::Off),
+
+            cutoff: FloatParam::new(
+                "Cutoff",
+                2000.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2)),
+
+            resonance: FloatParam::new("Resonance", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            cutoff_spray: FloatParam::new(
+                "Cutoff Spray",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 4.0 },
+            )
+            .with_unit(" oct")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            resonance_spray: FloatParam::new(
+                "Resonance Spray",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            fx_filter_mode: EnumParam::new("FX Filter", FilterMode::Off),
+
+            fx_cutoff: FloatParam::new(
+                "FX Cutoff",
+                2000.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2)),
+
+            fx_resonance: FloatParam::new(
+                "FX Resonance",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            drive: FloatParam::new("Drive", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            delay_time: FloatParam::new(
+                "Delay Time",
+                0.25,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 4.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_unit(" sec"),
+
+            delay_sync: EnumParam::new("Delay Sync", TempoSync::Free),
+
+            delay_feedback: FloatParam::new(
+                "Delay Feedback",
+                0.4,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 0.95,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            delay_mix: FloatParam::new("Delay Mix", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            reverb_size: FloatParam::new(
+                "Reverb Size",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            reverb_mix: FloatParam::new(
+                "Reverb Mix",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
         }
     }
 }
@@ -206,13 +1104,18 @@ struct GrainiacParams {
     #[persist = "audio-paths"]
     audio_paths: Arc<Mutex<Vec<Option<String>>>>,
 
+    /// Reads channels 2 to 16 as MPE member channels, each carrying the pitch
+    /// bend, pressure and timbre of a single note.
+    #[id = "mpe"]
+    mpe: BoolParam,
+
     #[nested(array, group = "instances")]
     instances: [InstanceParams; 2],
 }
 
 impl Default for Grainiac {
     fn default() -> Self {
-        let (sampler, buf_output) = Sampler::new(48000.0, 2);
+        let (sampler, buf_output) = Sampler::new(SamplerConfig::new(48000.0).instances(2));
         let (sender, receiver) = bounded(1);
 
         Self {
@@ -221,6 +1124,8 @@ impl Default for Grainiac {
             buf_output: Arc::new(Mutex::new(buf_output)),
             sender: Arc::new(sender),
             receiver,
+            input_buffers: [Vec::new(), Vec::new()],
+            sample_rate: Arc::new(AtomicCell::new(48000.0)),
         }
     }
 }
@@ -230,6 +1135,7 @@ impl Default for GrainiacParams {
         Self {
             editor_state: editor::default_state(),
             audio_paths: Arc::new(Mutex::new(vec![None; 2])),
+            mpe: BoolParam::new("MPE", false),
             instances: [(); 2].map(|_| InstanceParams::new()),
         }
     }
@@ -258,7 +1164,7 @@ impl Plugin for Grainiac {
         names: PortNames::const_default(),
     }];
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
 
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
@@ -282,19 +1188,29 @@ impl Plugin for Grainiac {
             self.params.editor_state.clone(),
             self.buf_output.clone(),
             self.sender.clone(),
+            self.sample_rate.clone(),
         )
     }
 
     fn initialize(
         &mut self,
         _audio_io_layout: &AudioIOLayout,
-        _buffer_config: &BufferConfig,
+        buffer_config: &BufferConfig,
         _context: &mut impl InitContext<Self>,
     ) -> bool {
+        self.sampler.set_sample_rate(buffer_config.sample_rate);
+        self.sample_rate.store(buffer_config.sample_rate);
+
+        for input_buffer in self.input_buffers.iter_mut() {
+            input_buffer.resize(buffer_config.max_buffer_size as usize, 0.0);
+        }
+
         let paths = self.params.audio_paths.lock().unwrap().clone();
         for (i, path) in paths.iter().enumerate() {
             if let Some(path_str) = path {
-                if let Some(samples) = utils::AudioHandler::open(PathBuf::from(path_str)) {
+                if let Some(samples) =
+                    utils::AudioHandler::open(PathBuf::from(path_str), buffer_config.sample_rate)
+                {
                     self.sampler.load_buf(samples, i);
                 }
             }
@@ -313,6 +1229,10 @@ impl Plugin for Grainiac {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        if let Some(tempo) = context.transport().tempo {
+            self.sampler.set_tempo(tempo as f32);
+        }
+
         for (i, instance) in self.params.instances.iter().enumerate() {
             self.sampler.set_loop_start(i, instance.loop_start.value());
             self.sampler
@@ -339,6 +1259,110 @@ impl Plugin for Grainiac {
             );
             self.sampler
                 .set_hold(i, instance.hold.value() == Hold::On);
+            self.sampler
+                .set_mode_from_preset(i, (instance.mode.value() != PlayMode::Grain) as u8);
+            self.sampler
+                .set_stretch(i, instance.mode.value() == PlayMode::Stretch);
+            self.sampler
+                .set_interpolation_from_preset(i, instance.interpolation.value().to_index() as u8);
+            self.sampler
+                .set_window_from_preset(i, instance.window.value().to_index() as u8);
+            self.sampler
+                .set_window_flatness(i, instance.window_flatness.value());
+            self.sampler
+                .set_window_skew(i, instance.window_skew.value());
+            self.sampler.set_voice_stealing_from_preset(
+                i,
+                instance.voice_stealing.value().to_index() as u8,
+            );
+            self.sampler.set_velocity_curve_from_preset(
+                i,
+                instance.velocity_curve.value().to_index() as u8,
+            );
+            self.sampler
+                .set_velocity_to_density(i, instance.velocity_to_density.value());
+            self.sampler
+                .set_velocity_to_spray(i, instance.velocity_to_spray.value());
+            self.sampler
+                .set_velocity_to_attack(i, instance.velocity_to_attack.value());
+            self.sampler.set_key_range(
+                i,
+                instance.key_low.value() as u8,
+                instance.key_high.value() as u8,
+            );
+            self.sampler.set_velocity_range(
+                i,
+                instance.velocity_low.value() as f32 / 127.0,
+                instance.velocity_high.value() as f32 / 127.0,
+            );
+            self.sampler
+                .set_midi_channel_from_preset(i, instance.midi_channel.value() as u8);
+            self.sampler
+                .set_bend_range(i, instance.bend_range.value() as f32);
+            self.sampler.set_mod_wheel_target_from_preset(
+                i,
+                instance.mod_wheel_target.value().to_index() as u8,
+            );
+            self.sampler
+                .set_mod_wheel_amount(i, instance.mod_wheel_amount.value());
+            for (lfo, params) in instance.lfos.iter().enumerate() {
+                self.sampler.set_lfo_shape_from_preset(
+                    i,
+                    lfo,
+                    params.shape.value().to_index() as u8,
+                );
+                self.sampler.set_lfo_target_from_preset(
+                    i,
+                    lfo,
+                    params.target.value().to_index() as u8,
+                );
+                self.sampler.set_lfo_rate(i, lfo, params.rate.value());
+                self.sampler.set_lfo_depth(i, lfo, params.depth.value());
+                self.sampler
+                    .set_lfo_sync_from_preset(i, lfo, params.sync.value().to_index() as u8);
+            }
+            self.sampler
+                .set_scheduling_from_preset(i, instance.scheduling.value().to_index() as u8);
+            self.sampler.set_jitter(i, instance.jitter.value());
+            self.sampler
+                .set_density_sync_from_preset(i, instance.density_sync.value().to_index() as u8);
+            self.sampler
+                .set_loop_beats_from_preset(i, instance.loop_beats.value() as u8);
+            self.sampler
+                .set_speed_bars_from_preset(i, instance.speed_bars.value() as u8);
+            self.sampler
+                .set_pitch_spray(i, instance.pitch_spray.value());
+            self.sampler
+                .set_scale_from_preset(i, instance.scale.value().to_index() as u8);
+            self.sampler
+                .set_custom_scale_from_preset(i, instance.custom_scale.value() as u16);
+            self.sampler
+                .set_reverse_probability(i, instance.reverse_probability.value());
+            self.sampler
+                .set_filter_mode_from_preset(i, instance.filter_mode.value().to_index() as u8);
+            self.sampler.set_cutoff(i, instance.cutoff.value());
+            self.sampler.set_resonance(i, instance.resonance.value());
+            self.sampler
+                .set_cutoff_spray(i, instance.cutoff_spray.value());
+            self.sampler
+                .set_resonance_spray(i, instance.resonance_spray.value());
+            self.sampler.set_fx_filter_mode_from_preset(
+                i,
+                instance.fx_filter_mode.value().to_index() as u8,
+            );
+            self.sampler.set_fx_cutoff(i, instance.fx_cutoff.value());
+            self.sampler
+                .set_fx_resonance(i, instance.fx_resonance.value());
+            self.sampler.set_drive(i, instance.drive.value());
+            self.sampler.set_delay_time(i, instance.delay_time.value());
+            self.sampler
+                .set_delay_sync_from_preset(i, instance.delay_sync.value().to_index() as u8);
+            self.sampler
+                .set_delay_feedback(i, instance.delay_feedback.value());
+            self.sampler.set_delay_mix(i, instance.delay_mix.value());
+            self.sampler
+                .set_reverb_size(i, instance.reverb_size.value());
+            self.sampler.set_reverb_mix(i, instance.reverb_mix.value());
         }
 
         if let Ok(msg) = self.receiver.try_recv() {
@@ -350,25 +1374,90 @@ impl Plugin for Grainiac {
             }
         }
 
+        let mpe = self.params.mpe.value();
         let mut next_event = context.next_event();
         while let Some(event) = next_event {
             match event {
-                NoteEvent::NoteOn { note, .. } => self.sampler.note_on(note as usize),
-                NoteEvent::NoteOff { note, .. } => self.sampler.note_off(note as usize),
-                _ => {
-                    nih_plug::nih_log!("{:?}", event)
+                NoteEvent::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                    ..
+                } => self.sampler.note_on(channel, note as usize, velocity),
+                NoteEvent::NoteOff { channel, note, .. } => {
+                    self.sampler.note_off(channel, note as usize)
                 }
+                NoteEvent::PolyTuning {
+                    channel,
+                    note,
+                    tuning,
+                    ..
+                } => self.sampler.note_expression(
+                    channel,
+                    note as usize,
+                    NoteExpression::Tuning(tuning),
+                ),
+                NoteEvent::PolyPressure {
+                    channel,
+                    note,
+                    pressure,
+                    ..
+                } => self.sampler.note_expression(
+                    channel,
+                    note as usize,
+                    NoteExpression::Pressure(pressure),
+                ),
+                NoteEvent::PolyBrightness {
+                    channel,
+                    note,
+                    brightness,
+                    ..
+                } => self.sampler.note_expression(
+                    channel,
+                    note as usize,
+                    NoteExpression::Brightness(brightness),
+                ),
+                // MPE lower zone, the first channel is the master channel and
+                // every other channel carries a single note. Without MPE every
+                // channel bends the instances listening on it
+                NoteEvent::MidiPitchBend { channel, value, .. } if mpe && channel > 0 => {
+                    self.sampler.channel_expression(
+                        channel,
+                        NoteExpression::Tuning((value * 2.0 - 1.0) * MPE_BEND_RANGE),
+                    )
+                }
+                NoteEvent::MidiChannelPressure {
+                    channel, pressure, ..
+                } if mpe && channel > 0 => self
+                    .sampler
+                    .channel_expression(channel, NoteExpression::Pressure(pressure)),
+                NoteEvent::MidiCC {
+                    channel, cc, value, ..
+                } if mpe && channel > 0 && cc == MPE_TIMBRE_CC => self
+                    .sampler
+                    .channel_expression(channel, NoteExpression::Brightness(value)),
+                NoteEvent::MidiPitchBend { channel, value, .. } => {
+                    self.sampler.pitch_bend(channel, value * 2.0 - 1.0)
+                }
+                NoteEvent::MidiCC {
+                    channel, cc, value, ..
+                } if cc == MOD_WHEEL_CC => self.sampler.mod_wheel(channel, value),
+                // logging formats and locks, so unhandled events are dropped
+                // silently on the audio thread
+                _ => {}
             }
             next_event = context.next_event();
         }
 
-        for channels in buffer.iter_samples() {
-            let mut sample_channels = channels.into_iter();
-            let stereo_slice = (
-                sample_channels.next().unwrap(),
-                sample_channels.next().unwrap(),
+        let num_samples = buffer.samples();
+        let [input_l, input_r] = &mut self.input_buffers;
+        if let [output_l, output_r] = buffer.as_slice() {
+            input_l[..num_samples].copy_from_slice(&output_l[..]);
+            input_r[..num_samples].copy_from_slice(&output_r[..]);
+            self.sampler.process_block(
+                (&input_l[..num_samples], &input_r[..num_samples]),
+                (&mut output_l[..], &mut output_r[..]),
             );
-            self.sampler.render(stereo_slice);
         }
 
         ProcessStatus::Normal
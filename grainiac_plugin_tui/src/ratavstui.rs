@@ -74,6 +74,7 @@ pub struct RatatuiEditor {
     pub params: Arc<GrainiacParams>,
     pub draw_data: Arc<Mutex<Output<Vec<DrawData>>>>,
     pub sender: Arc<Sender<FileMessage>>,
+    pub sample_rate: Arc<AtomicCell<f32>>,
 }
 
 impl Editor for RatatuiEditor {
@@ -88,6 +89,7 @@ impl Editor for RatatuiEditor {
         let params = self.params.clone();
         let draw_data = self.draw_data.clone();
         let sender = self.sender.clone();
+        let sample_rate = self.sample_rate.clone();
 
         let window = Window::open_parented(
             &parent,
@@ -97,7 +99,14 @@ impl Editor for RatatuiEditor {
                 scale: WindowScalePolicy::ScaleFactor(1.0),
             },
             move |window: &mut Window| -> RatatuiWindowHandler {
-                RatatuiWindowHandler::new(state.clone(), window, params, draw_data, sender)
+                RatatuiWindowHandler::new(
+                    state.clone(),
+                    window,
+                    params,
+                    draw_data,
+                    sender,
+                    sample_rate,
+                )
             },
         );
 
@@ -151,6 +160,7 @@ struct RatatuiWindowHandler {
     draw_data: Arc<Mutex<Output<Vec<DrawData>>>>,
     damaged: bool,
     sender: Arc<Sender<FileMessage>>,
+    sample_rate: Arc<AtomicCell<f32>>,
 }
 
 impl RatatuiWindowHandler {
@@ -160,6 +170,7 @@ impl RatatuiWindowHandler {
         params: Arc<GrainiacParams>,
         draw_data: Arc<Mutex<Output<Vec<DrawData>>>>,
         sender: Arc<Sender<FileMessage>>,
+        sample_rate: Arc<AtomicCell<f32>>,
     ) -> Self {
         let ctx = unsafe { softbuffer::Context::new(window) }.unwrap();
         let mut surface = unsafe { softbuffer::Surface::new(&ctx, window) }.unwrap();
@@ -207,6 +218,7 @@ impl RatatuiWindowHandler {
             terminal,
             draw_data,
             sender: sender.clone(),
+            sample_rate,
         }
     }
 }
@@ -309,7 +321,9 @@ impl WindowHandler for RatatuiWindowHandler {
                                 .pick_file();
 
                             if let Some(path) = file {
-                                if let Some(samples) = utils::AudioHandler::open(path) {
+                                if let Some(samples) =
+                                    utils::AudioHandler::open(path, self.sample_rate.load())
+                                {
                                     self.sender
                                         .send(FileMessage::LoadAudio(samples, 0))
                                         .unwrap();
@@ -323,7 +337,9 @@ impl WindowHandler for RatatuiWindowHandler {
                                 .pick_file();
 
                             if let Some(path) = file {
-                                if let Some(samples) = utils::AudioHandler::open(path) {
+                                if let Some(samples) =
+                                    utils::AudioHandler::open(path, self.sample_rate.load())
+                                {
                                     self.sender
                                         .send(FileMessage::LoadAudio(samples, 1))
                                         .unwrap();
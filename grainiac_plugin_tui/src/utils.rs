@@ -1,9 +1,12 @@
 use std::path::PathBuf;
 
+use grainiac_core::resample;
+
 pub struct AudioHandler {}
 
 impl AudioHandler {
-    pub fn open(path: PathBuf) -> Option<Vec<f32>> {
+    /// Reads a WAV file as stereo and resamples it to `sample_rate`.
+    pub fn open(path: PathBuf, sample_rate: f32) -> Option<[Vec<f32>; 2]> {
         if let Ok(mut reader) = hound::WavReader::open(path) {
             let spec = reader.spec();
 
@@ -29,14 +32,18 @@ impl AudioHandler {
                 hound::SampleFormat::Float => reader.samples::<f32>().map(|s| s.unwrap()).collect(),
             };
 
-            // Convert to mono by averaging across channels per frame
-            let mut mono_samples = Vec::with_capacity(raw_samples.len() / num_channels);
+            // Split into left and right, mono files are copied to both channels
+            let frame_num = raw_samples.len() / num_channels;
+            let mut stereo_samples = [Vec::with_capacity(frame_num), Vec::with_capacity(frame_num)];
             for frame in raw_samples.chunks(num_channels) {
-                let sum: f32 = frame.iter().copied().sum();
-                mono_samples.push(sum / num_channels as f32);
+                stereo_samples[0].push(frame[0]);
+                stereo_samples[1].push(frame[1.min(num_channels - 1)]);
             }
 
-            return Some(mono_samples);
+            let stereo_samples = stereo_samples
+                .map(|channel| resample(&channel, spec.sample_rate as f32, sample_rate));
+
+            return Some(stereo_samples);
         }
 
         None
@@ -9,7 +9,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use grainiac_core::Sampler;
+use grainiac_core::{NoteExpression, Sampler, SamplerConfig, LFO_NUM, MPE_BEND_RANGE};
 use jack::{AudioIn, AudioOut, Client, ClientOptions, MidiIn, Port};
 use ratatui::crossterm::{
     event::{KeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
@@ -21,11 +21,41 @@ mod ui;
 mod widgets;
 
 pub const INSTANCE_NUM: usize = 2;
+const MPE_TIMBRE_CC: u8 = 74;
+const MOD_WHEEL_CC: u8 = 1;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct Config {
     presets: Vec<Preset>,
     mapping: Mapping,
+    #[serde(default)]
+    engine: Engine,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+struct Engine {
+    voices: usize,
+    grains_per_voice: usize,
+    record_seconds: f32,
+    max_buffer_seconds: f32,
+    bars: usize,
+    /// Reads channels 2 to 16 as MPE member channels, each carrying the pitch
+    /// bend, pressure and timbre of a single note.
+    mpe: bool,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            voices: 16,
+            grains_per_voice: 256,
+            record_seconds: 10.0,
+            max_buffer_seconds: 180.0,
+            bars: 100,
+            mpe: false,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -46,10 +76,176 @@ pub struct Preset {
     play_dir: [u8; 4],
     grain_dir: [u8; 4],
     mode: [u8; 4],
+    #[serde(default)]
+    stretch: [bool; 4],
+    #[serde(default)]
+    interpolation: [u8; 4],
+    #[serde(default)]
+    window: [u8; 4],
+    #[serde(default)]
+    window_flatness: [f32; 4],
+    #[serde(default)]
+    window_skew: [f32; 4],
+    #[serde(default)]
+    voice_stealing: [u8; 4],
+    #[serde(default)]
+    velocity_curve: [u8; 4],
+    #[serde(default)]
+    velocity_to_density: [f32; 4],
+    #[serde(default)]
+    velocity_to_spray: [f32; 4],
+    #[serde(default)]
+    velocity_to_attack: [f32; 4],
+    #[serde(default)]
+    key_low: [u8; 4],
+    #[serde(default = "default_key_high")]
+    key_high: [u8; 4],
+    #[serde(default)]
+    velocity_low: [f32; 4],
+    #[serde(default = "default_velocity_high")]
+    velocity_high: [f32; 4],
+    #[serde(default)]
+    midi_channel: [u8; 4],
+    #[serde(default = "default_bend_range")]
+    bend_range: [f32; 4],
+    #[serde(default)]
+    mod_wheel_target: [u8; 4],
+    #[serde(default = "default_mod_wheel_amount")]
+    mod_wheel_amount: [f32; 4],
+    #[serde(default)]
+    lfo_shape: [[u8; LFO_NUM]; 4],
+    #[serde(default)]
+    lfo_target: [[u8; LFO_NUM]; 4],
+    #[serde(default = "default_lfo_rate")]
+    lfo_rate: [[f32; LFO_NUM]; 4],
+    #[serde(default = "default_lfo_depth")]
+    lfo_depth: [[f32; LFO_NUM]; 4],
+    #[serde(default)]
+    lfo_sync: [[u8; LFO_NUM]; 4],
+    #[serde(default)]
+    scheduling: [u8; 4],
+    #[serde(default = "default_jitter")]
+    jitter: [f32; 4],
+    #[serde(default)]
+    density_sync: [u8; 4],
+    #[serde(default)]
+    loop_beats: [u8; 4],
+    #[serde(default)]
+    speed_bars: [u8; 4],
+    #[serde(default)]
+    overdub: [bool; 4],
+    #[serde(default = "default_feedback")]
+    feedback: [f32; 4],
+    #[serde(default)]
+    punch: [bool; 4],
+    #[serde(default = "default_record_threshold")]
+    record_threshold: [f32; 4],
+    #[serde(default)]
+    live: [bool; 4],
+    #[serde(default = "default_live_delay")]
+    live_delay: [f32; 4],
+    #[serde(default)]
+    pitch_spray: [f32; 4],
+    #[serde(default)]
+    scale: [u8; 4],
+    #[serde(default = "default_scale_intervals")]
+    scale_intervals: [Vec<u8>; 4],
+    #[serde(default)]
+    reverse_probability: [f32; 4],
+    #[serde(default)]
+    filter: [u8; 4],
+    #[serde(default = "default_cutoff")]
+    cutoff: [f32; 4],
+    #[serde(default)]
+    resonance: [f32; 4],
+    #[serde(default)]
+    cutoff_spray: [f32; 4],
+    #[serde(default)]
+    resonance_spray: [f32; 4],
+    #[serde(default)]
+    fx_filter: [u8; 4],
+    #[serde(default = "default_cutoff")]
+    fx_cutoff: [f32; 4],
+    #[serde(default)]
+    fx_resonance: [f32; 4],
+    #[serde(default)]
+    drive: [f32; 4],
+    #[serde(default = "default_delay_time")]
+    delay_time: [f32; 4],
+    #[serde(default)]
+    delay_sync: [u8; 4],
+    #[serde(default = "default_delay_feedback")]
+    delay_feedback: [f32; 4],
+    #[serde(default)]
+    delay_mix: [f32; 4],
+    #[serde(default = "default_reverb_size")]
+    reverb_size: [f32; 4],
+    #[serde(default)]
+    reverb_mix: [f32; 4],
     name: String,
     char: char,
 }
 
+fn default_key_high() -> [u8; 4] {
+    [127; 4]
+}
+
+fn default_velocity_high() -> [f32; 4] {
+    [1.0; 4]
+}
+
+fn default_bend_range() -> [f32; 4] {
+    [2.0; 4]
+}
+
+fn default_mod_wheel_amount() -> [f32; 4] {
+    [1.0; 4]
+}
+
+fn default_jitter() -> [f32; 4] {
+    [0.5; 4]
+}
+
+fn default_feedback() -> [f32; 4] {
+    [1.0; 4]
+}
+
+fn default_record_threshold() -> [f32; 4] {
+    [0.1; 4]
+}
+
+fn default_live_delay() -> [f32; 4] {
+    [0.5; 4]
+}
+
+fn default_scale_intervals() -> [Vec<u8>; 4] {
+    [(); 4].map(|_| vec![0, 4, 7])
+}
+
+fn default_cutoff() -> [f32; 4] {
+    [2000.0; 4]
+}
+
+fn default_delay_time() -> [f32; 4] {
+    [0.25; 4]
+}
+
+fn default_delay_feedback() -> [f32; 4] {
+    [0.4; 4]
+}
+
+fn default_reverb_size() -> [f32; 4] {
+    [0.5; 4]
+}
+
+fn default_lfo_rate() -> [[f32; LFO_NUM]; 4] {
+    [[1.0; LFO_NUM]; 4]
+}
+
+fn default_lfo_depth() -> [[f32; LFO_NUM]; 4] {
+    [[0.5; LFO_NUM]; 4]
+}
+
 impl Preset {
     fn to_preview(&self) -> Vec<String> {
         let loop_start: Vec<String> = self.loop_start.iter().map(|p| p.to_string()).collect();
@@ -66,6 +262,92 @@ impl Preset {
         let play_dir: Vec<String> = self.play_dir.iter().map(|p| p.to_string()).collect();
         let grain_dir: Vec<String> = self.grain_dir.iter().map(|p| p.to_string()).collect();
         let gain: Vec<String> = self.gain.iter().map(|p| p.to_string()).collect();
+        let mode: Vec<String> = self.mode.iter().map(|p| p.to_string()).collect();
+        let stretch: Vec<String> = self.stretch.iter().map(|p| p.to_string()).collect();
+        let interpolation: Vec<String> = self.interpolation.iter().map(|p| p.to_string()).collect();
+        let window: Vec<String> = self.window.iter().map(|p| p.to_string()).collect();
+        let window_flatness: Vec<String> =
+            self.window_flatness.iter().map(|p| p.to_string()).collect();
+        let window_skew: Vec<String> = self.window_skew.iter().map(|p| p.to_string()).collect();
+        let voice_stealing: Vec<String> =
+            self.voice_stealing.iter().map(|p| p.to_string()).collect();
+        let velocity_curve: Vec<String> =
+            self.velocity_curve.iter().map(|p| p.to_string()).collect();
+        let velocity_to_density: Vec<String> = self
+            .velocity_to_density
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let velocity_to_spray: Vec<String> = self
+            .velocity_to_spray
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let velocity_to_attack: Vec<String> = self
+            .velocity_to_attack
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let key_low: Vec<String> = self.key_low.iter().map(|p| p.to_string()).collect();
+        let key_high: Vec<String> = self.key_high.iter().map(|p| p.to_string()).collect();
+        let velocity_low: Vec<String> = self.velocity_low.iter().map(|p| p.to_string()).collect();
+        let velocity_high: Vec<String> = self.velocity_high.iter().map(|p| p.to_string()).collect();
+        let midi_channel: Vec<String> = self.midi_channel.iter().map(|p| p.to_string()).collect();
+        let bend_range: Vec<String> = self.bend_range.iter().map(|p| p.to_string()).collect();
+        let mod_wheel_target: Vec<String> = self
+            .mod_wheel_target
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let mod_wheel_amount: Vec<String> = self
+            .mod_wheel_amount
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let scheduling: Vec<String> = self.scheduling.iter().map(|p| p.to_string()).collect();
+        let jitter: Vec<String> = self.jitter.iter().map(|p| p.to_string()).collect();
+        let density_sync: Vec<String> = self.density_sync.iter().map(|p| p.to_string()).collect();
+        let loop_beats: Vec<String> = self.loop_beats.iter().map(|p| p.to_string()).collect();
+        let speed_bars: Vec<String> = self.speed_bars.iter().map(|p| p.to_string()).collect();
+        let overdub: Vec<String> = self.overdub.iter().map(|p| p.to_string()).collect();
+        let feedback: Vec<String> = self.feedback.iter().map(|p| p.to_string()).collect();
+        let punch: Vec<String> = self.punch.iter().map(|p| p.to_string()).collect();
+        let record_threshold: Vec<String> = self
+            .record_threshold
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let live: Vec<String> = self.live.iter().map(|p| p.to_string()).collect();
+        let live_delay: Vec<String> = self.live_delay.iter().map(|p| p.to_string()).collect();
+        let pitch_spray: Vec<String> = self.pitch_spray.iter().map(|p| p.to_string()).collect();
+        let scale: Vec<String> = self.scale.iter().map(|p| p.to_string()).collect();
+        let scale_intervals: Vec<String> = self
+            .scale_intervals
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect();
+        let reverse_probability: Vec<String> = self
+            .reverse_probability
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let filter: Vec<String> = self.filter.iter().map(|p| p.to_string()).collect();
+        let cutoff: Vec<String> = self.cutoff.iter().map(|p| p.to_string()).collect();
+        let resonance: Vec<String> = self.resonance.iter().map(|p| p.to_string()).collect();
+        let cutoff_spray: Vec<String> = self.cutoff_spray.iter().map(|p| p.to_string()).collect();
+        let resonance_spray: Vec<String> =
+            self.resonance_spray.iter().map(|p| p.to_string()).collect();
+        let fx_filter: Vec<String> = self.fx_filter.iter().map(|p| p.to_string()).collect();
+        let fx_cutoff: Vec<String> = self.fx_cutoff.iter().map(|p| p.to_string()).collect();
+        let fx_resonance: Vec<String> = self.fx_resonance.iter().map(|p| p.to_string()).collect();
+        let drive: Vec<String> = self.drive.iter().map(|p| p.to_string()).collect();
+        let delay_time: Vec<String> = self.delay_time.iter().map(|p| p.to_string()).collect();
+        let delay_sync: Vec<String> = self.delay_sync.iter().map(|p| p.to_string()).collect();
+        let delay_feedback: Vec<String> =
+            self.delay_feedback.iter().map(|p| p.to_string()).collect();
+        let delay_mix: Vec<String> = self.delay_mix.iter().map(|p| p.to_string()).collect();
+        let reverb_size: Vec<String> = self.reverb_size.iter().map(|p| p.to_string()).collect();
+        let reverb_mix: Vec<String> = self.reverb_mix.iter().map(|p| p.to_string()).collect();
 
         vec![
             format!("start: {}", loop_start.join(", ")),
@@ -82,6 +364,60 @@ impl Preset {
             format!("vol: {}", gain.join(", ")),
             format!("pl_dir: {}", play_dir.join(", ")),
             format!("gr_dir: {}", grain_dir.join(", ")),
+            format!("mode: {}", mode.join(", ")),
+            format!("strch: {}", stretch.join(", ")),
+            format!("intp: {}", interpolation.join(", ")),
+            format!("win: {}", window.join(", ")),
+            format!("flat: {}", window_flatness.join(", ")),
+            format!("skew: {}", window_skew.join(", ")),
+            format!("steal: {}", voice_stealing.join(", ")),
+            format!("vcrv: {}", velocity_curve.join(", ")),
+            format!("v>dens: {}", velocity_to_density.join(", ")),
+            format!("v>spy: {}", velocity_to_spray.join(", ")),
+            format!("v>att: {}", velocity_to_attack.join(", ")),
+            format!("key_lo: {}", key_low.join(", ")),
+            format!("key_hi: {}", key_high.join(", ")),
+            format!("vel_lo: {}", velocity_low.join(", ")),
+            format!("vel_hi: {}", velocity_high.join(", ")),
+            format!("ch: {}", midi_channel.join(", ")),
+            format!("bend: {}", bend_range.join(", ")),
+            format!("mod: {}", mod_wheel_target.join(", ")),
+            format!("mod_amt: {}", mod_wheel_amount.join(", ")),
+            format!("lfo_shape: {:?}", self.lfo_shape),
+            format!("lfo_target: {:?}", self.lfo_target),
+            format!("lfo_rate: {:?}", self.lfo_rate),
+            format!("lfo_depth: {:?}", self.lfo_depth),
+            format!("lfo_sync: {:?}", self.lfo_sync),
+            format!("sched: {}", scheduling.join(", ")),
+            format!("jit: {}", jitter.join(", ")),
+            format!("dsync: {}", density_sync.join(", ")),
+            format!("beats: {}", loop_beats.join(", ")),
+            format!("bars: {}", speed_bars.join(", ")),
+            format!("dub: {}", overdub.join(", ")),
+            format!("fb: {}", feedback.join(", ")),
+            format!("punch: {}", punch.join(", ")),
+            format!("thr: {}", record_threshold.join(", ")),
+            format!("live: {}", live.join(", ")),
+            format!("ldel: {}", live_delay.join(", ")),
+            format!("pspy: {}", pitch_spray.join(", ")),
+            format!("scale: {}", scale.join(", ")),
+            format!("ivl: {}", scale_intervals.join(", ")),
+            format!("rev: {}", reverse_probability.join(", ")),
+            format!("filter: {}", filter.join(", ")),
+            format!("cut: {}", cutoff.join(", ")),
+            format!("res: {}", resonance.join(", ")),
+            format!("c spray: {}", cutoff_spray.join(", ")),
+            format!("r spray: {}", resonance_spray.join(", ")),
+            format!("fx filter: {}", fx_filter.join(", ")),
+            format!("fx cut: {}", fx_cutoff.join(", ")),
+            format!("fx res: {}", fx_resonance.join(", ")),
+            format!("drive: {}", drive.join(", ")),
+            format!("delay: {}", delay_time.join(", ")),
+            format!("dlsync: {}", delay_sync.join(", ")),
+            format!("dl fb: {}", delay_feedback.join(", ")),
+            format!("dl mix: {}", delay_mix.join(", ")),
+            format!("rv size: {}", reverb_size.join(", ")),
+            format!("rv mix: {}", reverb_mix.join(", ")),
         ]
     }
 }
@@ -105,14 +441,128 @@ struct Mapping {
     play_dir: u8,
     grain_dir: u8,
     mode: u8,
+    #[serde(default = "unmapped")]
+    stretch: u8,
+    #[serde(default = "unmapped")]
+    interpolation: u8,
+    #[serde(default = "unmapped")]
+    window: u8,
+    #[serde(default = "unmapped")]
+    window_flatness: u8,
+    #[serde(default = "unmapped")]
+    window_skew: u8,
+    #[serde(default = "unmapped")]
+    voice_stealing: u8,
+    #[serde(default = "unmapped")]
+    velocity_curve: u8,
+    #[serde(default = "unmapped")]
+    velocity_to_density: u8,
+    #[serde(default = "unmapped")]
+    velocity_to_spray: u8,
+    #[serde(default = "unmapped")]
+    velocity_to_attack: u8,
+    #[serde(default = "unmapped")]
+    bend_range: u8,
+    #[serde(default = "unmapped")]
+    mod_wheel_target: u8,
+    #[serde(default = "unmapped")]
+    mod_wheel_amount: u8,
+    #[serde(default = "unmapped_lfo")]
+    lfo_shape: [u8; LFO_NUM],
+    #[serde(default = "unmapped_lfo")]
+    lfo_target: [u8; LFO_NUM],
+    #[serde(default = "unmapped_lfo")]
+    lfo_rate: [u8; LFO_NUM],
+    #[serde(default = "unmapped_lfo")]
+    lfo_depth: [u8; LFO_NUM],
+    #[serde(default = "unmapped_lfo")]
+    lfo_sync: [u8; LFO_NUM],
+    #[serde(default = "unmapped")]
+    scheduling: u8,
+    #[serde(default = "unmapped")]
+    jitter: u8,
+    #[serde(default = "unmapped")]
+    density_sync: u8,
+    #[serde(default = "unmapped")]
+    loop_beats: u8,
+    #[serde(default = "unmapped")]
+    speed_bars: u8,
+    #[serde(default = "unmapped")]
+    overdub: u8,
+    #[serde(default = "unmapped")]
+    feedback: u8,
+    #[serde(default = "unmapped")]
+    punch: u8,
+    #[serde(default = "unmapped")]
+    record_gate: u8,
+    #[serde(default = "unmapped")]
+    arm: u8,
+    #[serde(default = "unmapped")]
+    record_threshold: u8,
+    #[serde(default = "unmapped")]
+    live: u8,
+    #[serde(default = "unmapped")]
+    freeze: u8,
+    #[serde(default = "unmapped")]
+    live_delay: u8,
+    #[serde(default = "unmapped")]
+    pitch_spray: u8,
+    #[serde(default = "unmapped")]
+    scale: u8,
+    #[serde(default = "unmapped")]
+    reverse_probability: u8,
+    #[serde(default = "unmapped")]
+    filter: u8,
+    #[serde(default = "unmapped")]
+    cutoff: u8,
+    #[serde(default = "unmapped")]
+    resonance: u8,
+    #[serde(default = "unmapped")]
+    cutoff_spray: u8,
+    #[serde(default = "unmapped")]
+    resonance_spray: u8,
+    #[serde(default = "unmapped")]
+    fx_filter: u8,
+    #[serde(default = "unmapped")]
+    fx_cutoff: u8,
+    #[serde(default = "unmapped")]
+    fx_resonance: u8,
+    #[serde(default = "unmapped")]
+    drive: u8,
+    #[serde(default = "unmapped")]
+    delay_time: u8,
+    #[serde(default = "unmapped")]
+    delay_sync: u8,
+    #[serde(default = "unmapped")]
+    delay_feedback: u8,
+    #[serde(default = "unmapped")]
+    delay_mix: u8,
+    #[serde(default = "unmapped")]
+    reverb_size: u8,
+    #[serde(default = "unmapped")]
+    reverb_mix: u8,
     select_l: u8,
     select_r: u8,
 }
 
+/// Controls missing from an older config stay unmapped, as no CC number goes
+/// above 127.
+fn unmapped() -> u8 {
+    u8::MAX
+}
+
+fn unmapped_lfo() -> [u8; LFO_NUM] {
+    [u8::MAX; LFO_NUM]
+}
+
 pub enum Msg {
     ApplyPreset(Preset),
     SaveAudio(char),
-    LoadAudio(char),
+    /// Audio of a single instance, decoded and resampled to the engine's
+    /// sample rate off the audio thread.
+    LoadAudio(usize, [Vec<f32>; 2]),
+    /// Audio of every instance back to back, as saved by older versions.
+    LoadAllAudio([Vec<f32>; 2]),
 }
 
 fn main() -> io::Result<()> {
@@ -142,7 +592,14 @@ fn main() -> io::Result<()> {
     let midi_in_port = client.register_port("midi_in", MidiIn::default()).unwrap();
 
     let sr = client.sample_rate() as f32;
-    let (sampler, out_buf) = Sampler::new(sr, INSTANCE_NUM);
+    let sampler_config = SamplerConfig::new(sr)
+        .instances(INSTANCE_NUM)
+        .voices(config.engine.voices)
+        .grains_per_voice(config.engine.grains_per_voice)
+        .max_buffer_seconds(config.engine.max_buffer_seconds)
+        .record_seconds(config.engine.record_seconds)
+        .bars(config.engine.bars);
+    let (sampler, out_buf) = Sampler::new(sampler_config);
 
     struct State {
         input_l: Port<AudioIn>,
@@ -166,7 +623,7 @@ fn main() -> io::Result<()> {
             receiver: r,
             config: config.clone(),
         },
-        |state, _, ps| -> jack::Control {
+        |state, client, ps| -> jack::Control {
             let output_l = state.output_l.as_mut_slice(ps);
             let output_r = state.output_r.as_mut_slice(ps);
             let input_l = state.input_l.as_slice(ps);
@@ -228,49 +685,309 @@ fn main() -> io::Result<()> {
                         for (i, v) in preset.grain_dir.iter().enumerate() {
                             state.sampler.set_grain_dir_from_preset(i, *v);
                         }
+
+                        for (i, v) in preset.mode.iter().enumerate() {
+                            state.sampler.set_mode_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.stretch.iter().enumerate() {
+                            state.sampler.set_stretch(i, *v);
+                        }
+
+                        for (i, v) in preset.interpolation.iter().enumerate() {
+                            state.sampler.set_interpolation_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.window.iter().enumerate() {
+                            state.sampler.set_window_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.window_flatness.iter().enumerate() {
+                            state.sampler.set_window_flatness(i, *v);
+                        }
+
+                        for (i, v) in preset.window_skew.iter().enumerate() {
+                            state.sampler.set_window_skew(i, *v);
+                        }
+
+                        for (i, v) in preset.voice_stealing.iter().enumerate() {
+                            state.sampler.set_voice_stealing_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.velocity_curve.iter().enumerate() {
+                            state.sampler.set_velocity_curve_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.velocity_to_density.iter().enumerate() {
+                            state.sampler.set_velocity_to_density(i, *v);
+                        }
+
+                        for (i, v) in preset.velocity_to_spray.iter().enumerate() {
+                            state.sampler.set_velocity_to_spray(i, *v);
+                        }
+
+                        for (i, v) in preset.velocity_to_attack.iter().enumerate() {
+                            state.sampler.set_velocity_to_attack(i, *v);
+                        }
+
+                        for (i, v) in preset.key_low.iter().enumerate() {
+                            state.sampler.set_key_range(i, *v, preset.key_high[i]);
+                        }
+
+                        for (i, v) in preset.velocity_low.iter().enumerate() {
+                            state
+                                .sampler
+                                .set_velocity_range(i, *v, preset.velocity_high[i]);
+                        }
+
+                        for (i, v) in preset.midi_channel.iter().enumerate() {
+                            state.sampler.set_midi_channel_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.bend_range.iter().enumerate() {
+                            state.sampler.set_bend_range(i, *v);
+                        }
+
+                        for (i, v) in preset.mod_wheel_target.iter().enumerate() {
+                            state.sampler.set_mod_wheel_target_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.mod_wheel_amount.iter().enumerate() {
+                            state.sampler.set_mod_wheel_amount(i, *v);
+                        }
+
+                        for (i, lfos) in preset.lfo_shape.iter().enumerate() {
+                            for (lfo, v) in lfos.iter().enumerate() {
+                                state.sampler.set_lfo_shape_from_preset(i, lfo, *v);
+                            }
+                        }
+
+                        for (i, lfos) in preset.lfo_target.iter().enumerate() {
+                            for (lfo, v) in lfos.iter().enumerate() {
+                                state.sampler.set_lfo_target_from_preset(i, lfo, *v);
+                            }
+                        }
+
+                        for (i, lfos) in preset.lfo_rate.iter().enumerate() {
+                            for (lfo, v) in lfos.iter().enumerate() {
+                                state.sampler.set_lfo_rate(i, lfo, *v);
+                            }
+                        }
+
+                        for (i, lfos) in preset.lfo_depth.iter().enumerate() {
+                            for (lfo, v) in lfos.iter().enumerate() {
+                                state.sampler.set_lfo_depth(i, lfo, *v);
+                            }
+                        }
+
+                        for (i, lfos) in preset.lfo_sync.iter().enumerate() {
+                            for (lfo, v) in lfos.iter().enumerate() {
+                                state.sampler.set_lfo_sync_from_preset(i, lfo, *v);
+                            }
+                        }
+
+                        for (i, v) in preset.scheduling.iter().enumerate() {
+                            state.sampler.set_scheduling_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.jitter.iter().enumerate() {
+                            state.sampler.set_jitter(i, *v);
+                        }
+
+                        for (i, v) in preset.density_sync.iter().enumerate() {
+                            state.sampler.set_density_sync_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.loop_beats.iter().enumerate() {
+                            state.sampler.set_loop_beats_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.speed_bars.iter().enumerate() {
+                            state.sampler.set_speed_bars_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.overdub.iter().enumerate() {
+                            state.sampler.set_overdub(i, *v);
+                        }
+
+                        for (i, v) in preset.feedback.iter().enumerate() {
+                            state.sampler.set_feedback(i, *v);
+                        }
+
+                        for (i, v) in preset.punch.iter().enumerate() {
+                            state.sampler.set_punch(i, *v);
+                        }
+
+                        for (i, v) in preset.record_threshold.iter().enumerate() {
+                            state.sampler.set_record_threshold(i, *v);
+                        }
+
+                        for (i, v) in preset.live.iter().enumerate() {
+                            state.sampler.set_live(i, *v);
+                        }
+
+                        for (i, v) in preset.live_delay.iter().enumerate() {
+                            state.sampler.set_live_delay(i, *v);
+                        }
+
+                        for (i, v) in preset.pitch_spray.iter().enumerate() {
+                            state.sampler.set_pitch_spray(i, *v);
+                        }
+
+                        for (i, v) in preset.scale.iter().enumerate() {
+                            state.sampler.set_scale_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.scale_intervals.iter().enumerate() {
+                            state.sampler.set_custom_scale(i, v);
+                        }
+
+                        for (i, v) in preset.reverse_probability.iter().enumerate() {
+                            state.sampler.set_reverse_probability(i, *v);
+                        }
+
+                        for (i, v) in preset.filter.iter().enumerate() {
+                            state.sampler.set_filter_mode_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.cutoff.iter().enumerate() {
+                            state.sampler.set_cutoff(i, *v);
+                        }
+
+                        for (i, v) in preset.resonance.iter().enumerate() {
+                            state.sampler.set_resonance(i, *v);
+                        }
+
+                        for (i, v) in preset.cutoff_spray.iter().enumerate() {
+                            state.sampler.set_cutoff_spray(i, *v);
+                        }
+
+                        for (i, v) in preset.resonance_spray.iter().enumerate() {
+                            state.sampler.set_resonance_spray(i, *v);
+                        }
+
+                        for (i, v) in preset.fx_filter.iter().enumerate() {
+                            state.sampler.set_fx_filter_mode_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.fx_cutoff.iter().enumerate() {
+                            state.sampler.set_fx_cutoff(i, *v);
+                        }
+
+                        for (i, v) in preset.fx_resonance.iter().enumerate() {
+                            state.sampler.set_fx_resonance(i, *v);
+                        }
+
+                        for (i, v) in preset.drive.iter().enumerate() {
+                            state.sampler.set_drive(i, *v);
+                        }
+
+                        for (i, v) in preset.delay_time.iter().enumerate() {
+                            state.sampler.set_delay_time(i, *v);
+                        }
+
+                        for (i, v) in preset.delay_sync.iter().enumerate() {
+                            state.sampler.set_delay_sync_from_preset(i, *v);
+                        }
+
+                        for (i, v) in preset.delay_feedback.iter().enumerate() {
+                            state.sampler.set_delay_feedback(i, *v);
+                        }
+
+                        for (i, v) in preset.delay_mix.iter().enumerate() {
+                            state.sampler.set_delay_mix(i, *v);
+                        }
+
+                        for (i, v) in preset.reverb_size.iter().enumerate() {
+                            state.sampler.set_reverb_size(i, *v);
+                        }
+
+                        for (i, v) in preset.reverb_mix.iter().enumerate() {
+                            state.sampler.set_reverb_mix(i, *v);
+                        }
                     }
 
                     Msg::SaveAudio(index) => {
                         let spec = hound::WavSpec {
-                            channels: 1,
-                            sample_rate: 48000,
+                            channels: 2,
+                            sample_rate: state.sampler.sample_rate() as u32,
                             bits_per_sample: 32,
                             sample_format: hound::SampleFormat::Float,
                         };
 
                         let home_dir = env::home_dir().unwrap();
                         let path = home_dir.join(".local/share/grainiac/");
-                        let file_name = format!("grainiac_{}.wav", index);
-                        let full_path = path.join(file_name);
-                        let mut writer = hound::WavWriter::create(full_path, spec).unwrap();
                         let bufs = state.sampler.get_bufs();
-                        for buf in bufs {
-                            for &sample in buf.iter() {
-                                writer.write_sample(sample).unwrap();
+                        for (i, [buf_l, buf_r]) in bufs.into_iter().enumerate() {
+                            let file_name = format!("grainiac_{}_{}.wav", index, i);
+                            let full_path = path.join(file_name);
+                            let mut writer = hound::WavWriter::create(full_path, spec).unwrap();
+                            for (&sample_l, &sample_r) in buf_l.iter().zip(buf_r.iter()) {
+                                writer.write_sample(sample_l).unwrap();
+                                writer.write_sample(sample_r).unwrap();
                             }
                         }
                     }
 
-                    Msg::LoadAudio(index) => {
-                        let home_dir = env::home_dir().unwrap();
-                        let path = home_dir.join(".local/share/grainiac/");
-                        let file_name = format!("grainiac_{}.wav", index);
-                        let full_path = path.join(file_name);
-
-                        if let Ok(mut reader) = hound::WavReader::open(full_path) {
-                            let samples: Vec<f32> =
-                                reader.samples::<f32>().map(|s| s.unwrap()).collect();
-                            state.sampler.load_bufs(samples);
-                        };
-                    }
+                    Msg::LoadAudio(index, bufs) => state.sampler.load_buf(bufs, index),
+                    Msg::LoadAllAudio(bufs) => state.sampler.load_bufs(bufs),
                 }
             }
 
+            if let Some(bbt) = client
+                .transport()
+                .query()
+                .ok()
+                .and_then(|transport| transport.pos.bbt())
+            {
+                state.sampler.set_tempo(bbt.bpm as f32);
+            }
+
+            let mpe = state.config.engine.mpe;
             for event in midi {
                 let (message_type, midi_channel) = parse_status_byte(event.bytes[0]);
                 match message_type {
-                    9 => state.sampler.note_on(event.bytes[1] as usize),
-                    8 => state.sampler.note_off(event.bytes[1] as usize),
+                    // a note on without velocity is a note off
+                    9 if event.bytes[2] == 0 => state
+                        .sampler
+                        .note_off(midi_channel, event.bytes[1] as usize),
+                    9 => state.sampler.note_on(
+                        midi_channel,
+                        event.bytes[1] as usize,
+                        event.bytes[2] as f32 / 127.0,
+                    ),
+                    8 => state
+                        .sampler
+                        .note_off(midi_channel, event.bytes[1] as usize),
+                    // MPE lower zone, the first channel is the master channel
+                    // and every other channel carries a single note
+                    11 if mpe && midi_channel > 0 && event.bytes[1] == MPE_TIMBRE_CC => {
+                        state.sampler.channel_expression(
+                            midi_channel,
+                            NoteExpression::Brightness(event.bytes[2] as f32 / 127.0),
+                        )
+                    }
+                    13 if mpe && midi_channel > 0 => state.sampler.channel_expression(
+                        midi_channel,
+                        NoteExpression::Pressure(event.bytes[1] as f32 / 127.0),
+                    ),
+                    14 if mpe && midi_channel > 0 => {
+                        let bend = ((event.bytes[2] as u16) << 7 | event.bytes[1] as u16) as f32;
+                        state.sampler.channel_expression(
+                            midi_channel,
+                            NoteExpression::Tuning((bend - 8192.0) / 8192.0 * MPE_BEND_RANGE),
+                        )
+                    }
+                    // without MPE every channel bends the instances listening on it
+                    14 => {
+                        let bend = ((event.bytes[2] as u16) << 7 | event.bytes[1] as u16) as f32;
+                        state
+                            .sampler
+                            .pitch_bend(midi_channel, (bend - 8192.0) / 8192.0)
+                    }
+                    11 if event.bytes[1] == MOD_WHEEL_CC => state
+                        .sampler
+                        .mod_wheel(midi_channel, event.bytes[2] as f32 / 127.0),
                     11 => handle_midi_cc(
                         event.bytes[1],
                         event.bytes[2],
@@ -282,12 +999,9 @@ fn main() -> io::Result<()> {
                 }
             }
 
-            output_l.copy_from_slice(input_l);
-            output_r.copy_from_slice(input_r);
-
-            for (sample_l, sample_r) in output_l.iter_mut().zip(output_r.iter_mut()) {
-                state.sampler.render((sample_l, sample_r));
-            }
+            state
+                .sampler
+                .process_block((input_l, input_r), (output_l, output_r));
 
             jack::Control::Continue
         },
@@ -312,7 +1026,7 @@ fn main() -> io::Result<()> {
         .connect_ports_by_name("system:capture_2", "grainiac:input_r")
         .unwrap_or_default();
 
-    let mut state = state::State::new(out_buf, s.clone(), config.presets);
+    let mut state = state::State::new(out_buf, s.clone(), config.presets, sr);
     let mut terminal = ratatui::init();
     let mut stdout = stdout();
 
@@ -380,7 +1094,7 @@ fn handle_midi_cc(cc: u8, val: u8, instance: usize, sampler: &mut Sampler, mappi
         }
         x if x == mapping.record => {
             if value > 0.0 {
-                sampler.record(instance);
+                sampler.toggle_record(instance);
             }
         }
         x if x == mapping.hold => {
@@ -403,6 +1117,164 @@ fn handle_midi_cc(cc: u8, val: u8, instance: usize, sampler: &mut Sampler, mappi
                 sampler.toggle_mode(instance);
             }
         }
+        x if x == mapping.stretch && value > 0.0 => {
+            sampler.toggle_stretch(instance);
+        }
+        x if x == mapping.interpolation && value > 0.0 => {
+            sampler.toggle_interpolation(instance);
+        }
+        x if x == mapping.window && value > 0.0 => {
+            sampler.toggle_window(instance);
+        }
+        x if x == mapping.window_flatness => {
+            sampler.set_window_flatness(instance, value);
+        }
+        x if x == mapping.window_skew => {
+            sampler.set_window_skew(instance, (value * 2.0) - 1.0);
+        }
+        x if x == mapping.voice_stealing && value > 0.0 => {
+            sampler.toggle_voice_stealing(instance);
+        }
+        x if x == mapping.velocity_curve && value > 0.0 => {
+            sampler.toggle_velocity_curve(instance);
+        }
+        x if x == mapping.velocity_to_density => {
+            sampler.set_velocity_to_density(instance, value);
+        }
+        x if x == mapping.velocity_to_spray => {
+            sampler.set_velocity_to_spray(instance, value);
+        }
+        x if x == mapping.velocity_to_attack => {
+            sampler.set_velocity_to_attack(instance, value);
+        }
+        x if x == mapping.bend_range => {
+            sampler.set_bend_range(instance, (value * 24.0).round());
+        }
+        x if x == mapping.mod_wheel_target && value > 0.0 => {
+            sampler.toggle_mod_wheel_target(instance);
+        }
+        x if x == mapping.mod_wheel_amount => {
+            sampler.set_mod_wheel_amount(instance, (value * 2.0) - 1.0);
+        }
+        x if mapping.lfo_shape.contains(&x) && value > 0.0 => {
+            sampler.toggle_lfo_shape(instance, lfo_index(&mapping.lfo_shape, x));
+        }
+        x if mapping.lfo_target.contains(&x) && value > 0.0 => {
+            sampler.toggle_lfo_target(instance, lfo_index(&mapping.lfo_target, x));
+        }
+        x if mapping.lfo_rate.contains(&x) => {
+            // squared for finer control of slow rates, up to 20 Hz
+            let lfo = lfo_index(&mapping.lfo_rate, x);
+            sampler.set_lfo_rate(instance, lfo, value * value * 20.0);
+        }
+        x if mapping.lfo_depth.contains(&x) => {
+            let lfo = lfo_index(&mapping.lfo_depth, x);
+            sampler.set_lfo_depth(instance, lfo, (value * 2.0) - 1.0);
+        }
+        x if mapping.lfo_sync.contains(&x) && value > 0.0 => {
+            sampler.toggle_lfo_sync(instance, lfo_index(&mapping.lfo_sync, x));
+        }
+        x if x == mapping.scheduling && value > 0.0 => {
+            sampler.toggle_scheduling(instance);
+        }
+        x if x == mapping.jitter => {
+            sampler.set_jitter(instance, value);
+        }
+        x if x == mapping.density_sync && value > 0.0 => {
+            sampler.toggle_density_sync(instance);
+        }
+        x if x == mapping.loop_beats => {
+            sampler.set_loop_beats_from_preset(instance, (value * 32.0).round() as u8);
+        }
+        x if x == mapping.speed_bars => {
+            sampler.set_speed_bars_from_preset(instance, (value * 16.0).round() as u8);
+        }
+        x if x == mapping.overdub && value > 0.0 => {
+            sampler.toggle_overdub(instance);
+        }
+        x if x == mapping.feedback => {
+            sampler.set_feedback(instance, value);
+        }
+        x if x == mapping.punch && value > 0.0 => {
+            sampler.toggle_punch(instance);
+        }
+        x if x == mapping.record_gate => {
+            if value > 0.0 {
+                sampler.toggle_record(instance);
+            } else {
+                sampler.stop_recording(instance);
+            }
+        }
+        x if x == mapping.arm && value > 0.0 => {
+            sampler.toggle_arm(instance);
+        }
+        x if x == mapping.record_threshold => {
+            sampler.set_record_threshold(instance, value);
+        }
+        x if x == mapping.live && value > 0.0 => {
+            sampler.toggle_live(instance);
+        }
+        x if x == mapping.freeze && value > 0.0 => {
+            sampler.toggle_freeze(instance);
+        }
+        x if x == mapping.live_delay => {
+            let seconds = value * sampler.config().record_seconds;
+            sampler.set_live_delay(instance, seconds);
+        }
+        x if x == mapping.pitch_spray => {
+            sampler.set_pitch_spray(instance, value * 24.0);
+        }
+        x if x == mapping.scale && value > 0.0 => {
+            sampler.toggle_scale(instance);
+        }
+        x if x == mapping.reverse_probability => {
+            sampler.set_reverse_probability(instance, value);
+        }
+        x if x == mapping.filter && value > 0.0 => {
+            sampler.toggle_filter_mode(instance);
+        }
+        x if x == mapping.cutoff => {
+            sampler.set_cutoff(instance, 20.0 * 1000.0f32.powf(value));
+        }
+        x if x == mapping.resonance => {
+            sampler.set_resonance(instance, value);
+        }
+        x if x == mapping.cutoff_spray => {
+            sampler.set_cutoff_spray(instance, value * 4.0);
+        }
+        x if x == mapping.resonance_spray => {
+            sampler.set_resonance_spray(instance, value);
+        }
+        x if x == mapping.fx_filter && value > 0.0 => {
+            sampler.toggle_fx_filter_mode(instance);
+        }
+        x if x == mapping.fx_cutoff => {
+            sampler.set_fx_cutoff(instance, 20.0 * 1000.0f32.powf(value));
+        }
+        x if x == mapping.fx_resonance => {
+            sampler.set_fx_resonance(instance, value);
+        }
+        x if x == mapping.drive => {
+            sampler.set_drive(instance, value);
+        }
+        x if x == mapping.delay_time => {
+            sampler.set_delay_time(instance, value * 4.0);
+        }
+        x if x == mapping.delay_sync && value > 0.0 => {
+            sampler.toggle_delay_sync(instance);
+        }
+        x if x == mapping.delay_feedback => {
+            sampler.set_delay_feedback(instance, value);
+        }
+        x if x == mapping.delay_mix => {
+            sampler.set_delay_mix(instance, value);
+        }
+        x if x == mapping.reverb_size => {
+            sampler.set_reverb_size(instance, value);
+        }
+        x if x == mapping.reverb_mix => {
+            sampler.set_reverb_mix(instance, value);
+        }
         x if x == mapping.select_l => {
             let select = value > 0.0;
             sampler.set_select_l(select);
@@ -416,6 +1288,10 @@ fn handle_midi_cc(cc: u8, val: u8, instance: usize, sampler: &mut Sampler, mappi
     }
 }
 
+fn lfo_index(ccs: &[u8; LFO_NUM], cc: u8) -> usize {
+    ccs.iter().position(|c| *c == cc).unwrap_or_default()
+}
+
 fn parse_status_byte(status: u8) -> (u8, u8) {
     let message_type = (status & 0xF0) >> 4; // Upper 4 bits
     let channel = status & 0x0F; // Lower 4 bits
@@ -2,11 +2,12 @@ use std::{
     env,
     fs::{self, File},
     io::{self, BufReader},
+    path::PathBuf,
     time::Duration,
 };
 
 use crossbeam::channel::Sender;
-use grainiac_core::{DrawData, Output};
+use grainiac_core::{resample, DrawData, Output};
 use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
 
 use crate::{Config, Msg, Preset};
@@ -57,10 +58,16 @@ pub struct State {
     pub selected_preset_idx: usize,
     pub selectes_audio_idx: usize,
     pub s: Sender<Msg>,
+    sample_rate: f32,
 }
 
 impl State {
-    pub fn new(out_buf: Output<Vec<DrawData>>, s: Sender<Msg>, presets: Vec<Preset>) -> Self {
+    pub fn new(
+        out_buf: Output<Vec<DrawData>>,
+        s: Sender<Msg>,
+        presets: Vec<Preset>,
+        sample_rate: f32,
+    ) -> Self {
         Self {
             exiting: false,
             view: View::Main,
@@ -70,6 +77,7 @@ impl State {
             selectes_audio_idx: 0,
             presets,
             s,
+            sample_rate,
         }
     }
 
@@ -102,7 +110,7 @@ impl State {
                             }
                         }
                         NumMode::SavePreset => self.save_preset(c),
-                        NumMode::LoadAudio => self.s.send(Msg::LoadAudio(c)).unwrap(),
+                        NumMode::LoadAudio => self.load_audio(c),
                         NumMode::SaveAudio => self.s.send(Msg::SaveAudio(c)).unwrap(),
                     }
                 }
@@ -145,6 +153,48 @@ impl State {
         }
     }
 
+    /// Decodes and resamples here, as both take far too long for the audio
+    /// thread. Each instance is saved to its own file, so it gets back the
+    /// length it was saved with.
+    fn load_audio(&mut self, char: char) {
+        let home_dir = env::home_dir().unwrap();
+        let path = home_dir.join(".local/share/grainiac/");
+        let instance_num = self.out_buf.read().len();
+
+        let mut found = false;
+        for index in 0..instance_num {
+            let file_name = format!("grainiac_{}_{}.wav", char, index);
+            if let Some(bufs) = self.read_audio(path.join(file_name)) {
+                self.s.send(Msg::LoadAudio(index, bufs)).unwrap();
+                found = true;
+            }
+        }
+
+        // files saved before were all instances back to back
+        if !found {
+            let file_name = format!("grainiac_{}.wav", char);
+            if let Some(bufs) = self.read_audio(path.join(file_name)) {
+                self.s.send(Msg::LoadAllAudio(bufs)).unwrap();
+            }
+        }
+    }
+
+    fn read_audio(&self, path: PathBuf) -> Option<[Vec<f32>; 2]> {
+        let mut reader = hound::WavReader::open(path).ok()?;
+        let spec = reader.spec();
+        let channels = spec.channels as usize;
+        let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+
+        // mono files are loaded onto both channels
+        let mut bufs = [Vec::new(), Vec::new()];
+        for frame in samples.chunks(channels) {
+            bufs[0].push(frame[0]);
+            bufs[1].push(frame[1.min(channels - 1)]);
+        }
+
+        Some(bufs.map(|buf| resample(&buf, spec.sample_rate as f32, self.sample_rate)))
+    }
+
     fn save_preset(&mut self, char: char) {
         let data = self.out_buf.read();
         let mut new_preset = Preset::default();
@@ -164,11 +214,125 @@ impl State {
             new_preset.play_dir[i] = match track.state.play_dir {
                 grainiac_core::voice::PlayDirection::Forward => 0,
                 grainiac_core::voice::PlayDirection::Backward => 1,
+                grainiac_core::voice::PlayDirection::BackAndForth => 2,
             };
             new_preset.grain_dir[i] = match track.state.grain_dir {
                 grainiac_core::voice::PlayDirection::Forward => 0,
                 grainiac_core::voice::PlayDirection::Backward => 1,
+                grainiac_core::voice::PlayDirection::BackAndForth => 2,
+            };
+            new_preset.mode[i] = match track.state.mode {
+                grainiac_core::Mode::Grain => 0,
+                grainiac_core::Mode::Tape => 1,
             };
+            new_preset.stretch[i] = track.state.is_stretch;
+            new_preset.interpolation[i] = match track.state.interpolation {
+                grainiac_core::Interpolation::Linear => 0,
+                grainiac_core::Interpolation::Hermite => 1,
+                grainiac_core::Interpolation::Sinc => 2,
+            };
+            new_preset.window[i] = match track.state.window {
+                grainiac_core::WindowShape::Sine => 0,
+                grainiac_core::WindowShape::Hann => 1,
+                grainiac_core::WindowShape::Gaussian => 2,
+                grainiac_core::WindowShape::Tukey => 3,
+                grainiac_core::WindowShape::Trapezoid => 4,
+                grainiac_core::WindowShape::Exponential => 5,
+                grainiac_core::WindowShape::ReverseExponential => 6,
+            };
+            new_preset.window_flatness[i] = track.state.window_flatness;
+            new_preset.window_skew[i] = track.state.window_skew;
+            new_preset.voice_stealing[i] = match track.state.voice_stealing {
+                grainiac_core::VoiceStealing::Oldest => 0,
+                grainiac_core::VoiceStealing::Quietest => 1,
+                grainiac_core::VoiceStealing::SameNote => 2,
+                grainiac_core::VoiceStealing::LowestPriority => 3,
+                grainiac_core::VoiceStealing::HighestPriority => 4,
+            };
+            new_preset.velocity_curve[i] = match track.state.velocity_curve {
+                grainiac_core::VelocityCurve::Linear => 0,
+                grainiac_core::VelocityCurve::Soft => 1,
+                grainiac_core::VelocityCurve::Hard => 2,
+                grainiac_core::VelocityCurve::Fixed => 3,
+            };
+            new_preset.velocity_to_density[i] = track.state.velocity_to_density;
+            new_preset.velocity_to_spray[i] = track.state.velocity_to_spray;
+            new_preset.velocity_to_attack[i] = track.state.velocity_to_attack;
+            new_preset.key_low[i] = track.state.key_low;
+            new_preset.key_high[i] = track.state.key_high;
+            new_preset.velocity_low[i] = track.state.velocity_low;
+            new_preset.velocity_high[i] = track.state.velocity_high;
+            new_preset.midi_channel[i] = track.state.midi_channel.map_or(0, |channel| channel + 1);
+            new_preset.bend_range[i] = track.state.bend_range;
+            new_preset.mod_wheel_target[i] = mod_target_to_preset(track.state.mod_wheel_target);
+            new_preset.mod_wheel_amount[i] = track.state.mod_wheel_amount;
+            new_preset.lfo_shape[i] = track.state.lfos.map(|lfo| match lfo.shape {
+                grainiac_core::LfoShape::Sine => 0,
+                grainiac_core::LfoShape::Triangle => 1,
+                grainiac_core::LfoShape::Saw => 2,
+                grainiac_core::LfoShape::Square => 3,
+                grainiac_core::LfoShape::SampleAndHold => 4,
+                grainiac_core::LfoShape::SmoothRandom => 5,
+            });
+            new_preset.lfo_target[i] = track.state.lfos.map(|lfo| mod_target_to_preset(lfo.target));
+            new_preset.lfo_rate[i] = track.state.lfos.map(|lfo| lfo.rate);
+            new_preset.lfo_depth[i] = track.state.lfos.map(|lfo| lfo.depth);
+            new_preset.lfo_sync[i] = track.state.lfos.map(|lfo| division_to_preset(lfo.sync));
+            new_preset.scheduling[i] = match track.state.scheduling {
+                grainiac_core::GrainScheduling::Synchronous => 0,
+                grainiac_core::GrainScheduling::Asynchronous => 1,
+                grainiac_core::GrainScheduling::Jittered => 2,
+            };
+            new_preset.jitter[i] = track.state.jitter;
+            new_preset.density_sync[i] = division_to_preset(track.state.density_sync);
+            new_preset.loop_beats[i] = track.state.loop_beats.unwrap_or(0);
+            new_preset.speed_bars[i] = track.state.speed_bars.unwrap_or(0);
+            new_preset.overdub[i] = track.state.is_overdub;
+            new_preset.feedback[i] = track.state.feedback;
+            new_preset.punch[i] = track.state.is_punch;
+            new_preset.record_threshold[i] = track.state.record_threshold;
+            new_preset.live[i] = track.state.is_live;
+            new_preset.live_delay[i] = track.state.live_delay;
+            new_preset.pitch_spray[i] = track.state.pitch_spray;
+            new_preset.scale[i] = match track.state.scale {
+                grainiac_core::Scale::Free => 0,
+                grainiac_core::Scale::Chromatic => 1,
+                grainiac_core::Scale::Major => 2,
+                grainiac_core::Scale::Minor => 3,
+                grainiac_core::Scale::Pentatonic => 4,
+                grainiac_core::Scale::WholeTone => 5,
+                grainiac_core::Scale::Fifths => 6,
+                grainiac_core::Scale::Custom => 7,
+            };
+            new_preset.scale_intervals[i] = (0..12)
+                .filter(|interval| track.state.custom_scale & 1 << interval != 0)
+                .collect();
+            new_preset.reverse_probability[i] = track.state.reverse_probability;
+            new_preset.filter[i] = match track.state.filter_mode {
+                grainiac_core::FilterMode::Off => 0,
+                grainiac_core::FilterMode::LowPass => 1,
+                grainiac_core::FilterMode::BandPass => 2,
+                grainiac_core::FilterMode::HighPass => 3,
+            };
+            new_preset.cutoff[i] = track.state.cutoff;
+            new_preset.resonance[i] = track.state.resonance;
+            new_preset.cutoff_spray[i] = track.state.cutoff_spray;
+            new_preset.resonance_spray[i] = track.state.resonance_spray;
+            new_preset.fx_filter[i] = match track.state.fx_filter_mode {
+                grainiac_core::FilterMode::Off => 0,
+                grainiac_core::FilterMode::LowPass => 1,
+                grainiac_core::FilterMode::BandPass => 2,
+                grainiac_core::FilterMode::HighPass => 3,
+            };
+            new_preset.fx_cutoff[i] = track.state.fx_cutoff;
+            new_preset.fx_resonance[i] = track.state.fx_resonance;
+            new_preset.drive[i] = track.state.drive;
+            new_preset.delay_time[i] = track.state.delay_time;
+            new_preset.delay_sync[i] = division_to_preset(track.state.delay_sync);
+            new_preset.delay_feedback[i] = track.state.delay_feedback;
+            new_preset.delay_mix[i] = track.state.delay_mix;
+            new_preset.reverb_size[i] = track.state.reverb_size;
+            new_preset.reverb_mix[i] = track.state.reverb_mix;
             new_preset.name = format!("preset_{}", char);
             new_preset.char = char;
         }
@@ -197,3 +361,32 @@ impl State {
         self.num_mode = NumMode::LoadPreset;
     }
 }
+
+fn mod_target_to_preset(target: grainiac_core::ModTarget) -> u8 {
+    match target {
+        grainiac_core::ModTarget::Off => 0,
+        grainiac_core::ModTarget::LoopStart => 1,
+        grainiac_core::ModTarget::LoopLength => 2,
+        grainiac_core::ModTarget::Density => 3,
+        grainiac_core::ModTarget::GrainLength => 4,
+        grainiac_core::ModTarget::Spray => 5,
+        grainiac_core::ModTarget::Pan => 6,
+        grainiac_core::ModTarget::Spread => 7,
+        grainiac_core::ModTarget::Pitch => 8,
+        grainiac_core::ModTarget::Gain => 9,
+    }
+}
+
+fn division_to_preset(division: Option<grainiac_core::Division>) -> u8 {
+    match division {
+        None => 0,
+        Some(grainiac_core::Division::FourBars) => 1,
+        Some(grainiac_core::Division::TwoBars) => 2,
+        Some(grainiac_core::Division::Bar) => 3,
+        Some(grainiac_core::Division::Half) => 4,
+        Some(grainiac_core::Division::Quarter) => 5,
+        Some(grainiac_core::Division::Eighth) => 6,
+        Some(grainiac_core::Division::Sixteenth) => 7,
+        Some(grainiac_core::Division::ThirtySecond) => 8,
+    }
+}
@@ -1,5 +1,8 @@
 use brailles::{NUM_STATES, STATES, STATE_10};
-use grainiac_core::{instance::Mode, voice::PlayDirection, DrawData};
+use grainiac_core::{
+    instance::Mode, voice::PlayDirection, DrawData, GrainScheduling, Interpolation, ModTarget,
+    VoiceStealing,
+};
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
@@ -93,8 +96,10 @@ impl Widget for Track {
         // draw loop length
         for (index, char) in STATE_10.iter().enumerate() {
             let char_str = char.to_string();
-            let loop_start = self.draw_data.state.loop_start * self.draw_data.buffer.len() as f32;
-            let loop_length = (self.draw_data.state.loop_length + self.draw_data.state.loop_start)
+            let loop_start =
+                self.draw_data.modulated.loop_start * self.draw_data.buffer.len() as f32;
+            let loop_length = (self.draw_data.modulated.loop_length
+                + self.draw_data.modulated.loop_start)
                 * self.draw_data.buffer.len() as f32;
             buf[(
                 loop_start as u16 + layout[1].left(),
@@ -121,29 +126,79 @@ impl Widget for Track {
 
         let is_rec = if self.draw_data.state.is_recording {
             "[X]"
+        } else if self.draw_data.state.is_armed {
+            "[A]"
+        } else if self.draw_data.state.is_live && self.draw_data.state.is_frozen {
+            "[F]"
+        } else if self.draw_data.state.is_live {
+            "[L]"
         } else {
             "[ ]"
         };
-        let mode = if self.draw_data.state.mode == Mode::Grain {
-            "grain"
-        } else {
-            "tape"
+        let rec_mode = match (
+            self.draw_data.state.is_overdub,
+            self.draw_data.state.is_punch,
+        ) {
+            (false, false) => "",
+            (true, false) => " dub",
+            (false, true) => " punch",
+            (true, true) => " dub punch",
+        };
+        let mode = match self.draw_data.state.mode {
+            Mode::Grain => "grain",
+            Mode::Tape if self.draw_data.state.is_stretch => "stretch",
+            Mode::Tape => "tape",
         };
 
         let play_dir = match self.draw_data.state.play_dir {
             PlayDirection::Forward => ">>",
             PlayDirection::Backward => "<<",
+            PlayDirection::BackAndForth => "<>",
         };
         let grain_dir = match self.draw_data.state.grain_dir {
             PlayDirection::Forward => ">>",
             PlayDirection::Backward => "<<",
+            PlayDirection::BackAndForth => "<>",
+        };
+
+        let interpolation = match self.draw_data.state.interpolation {
+            Interpolation::Linear => "lin",
+            Interpolation::Hermite => "herm",
+            Interpolation::Sinc => "sinc",
+        };
+
+        let voice_stealing = match self.draw_data.state.voice_stealing {
+            VoiceStealing::Oldest => "old",
+            VoiceStealing::Quietest => "quiet",
+            VoiceStealing::SameNote => "same",
+            VoiceStealing::LowestPriority => "low",
+            VoiceStealing::HighestPriority => "high",
+        };
+
+        let scheduling = match self.draw_data.state.scheduling {
+            GrainScheduling::Synchronous => "sync",
+            GrainScheduling::Asynchronous => "async",
+            GrainScheduling::Jittered => "jitter",
+        };
+
+        let mod_wheel_target = match self.draw_data.state.mod_wheel_target {
+            ModTarget::Off => "off",
+            ModTarget::LoopStart => "start",
+            ModTarget::LoopLength => "length",
+            ModTarget::Density => "den",
+            ModTarget::GrainLength => "len",
+            ModTarget::Spray => "spy",
+            ModTarget::Pan => "pan",
+            ModTarget::Spread => "spr",
+            ModTarget::Pitch => "pch",
+            ModTarget::Gain => "vol",
         };
 
         let text = Text::from(Line::from(vec![
             Span::styled(self.label, Style::default().bold()),
             Span::styled("  Rec: ", Style::default().bold()),
             Span::styled(
-                is_rec,
+                format!("{is_rec}{rec_mode}"),
                 Style::default().fg(Color::Rgb(186, 225, 255)).bold(),
             ),
             Span::styled("  | ", Style::default().bold()),
@@ -167,32 +222,62 @@ impl Widget for Track {
             Span::styled("  | ", Style::default().bold()),
             Span::styled("Mode: ", Style::default().bold()),
             Span::styled(mode, Style::default().fg(Color::Rgb(186, 225, 255)).bold()),
+            Span::styled("  | ", Style::default().bold()),
+            Span::styled("Intp: ", Style::default().bold()),
+            Span::styled(
+                interpolation,
+                Style::default().fg(Color::Rgb(186, 225, 255)).bold(),
+            ),
+            Span::styled("  | ", Style::default().bold()),
+            Span::styled("Steal: ", Style::default().bold()),
+            Span::styled(
+                voice_stealing,
+                Style::default().fg(Color::Rgb(186, 225, 255)).bold(),
+            ),
+            Span::styled("  | ", Style::default().bold()),
+            Span::styled("Sched: ", Style::default().bold()),
+            Span::styled(
+                scheduling,
+                Style::default().fg(Color::Rgb(186, 225, 255)).bold(),
+            ),
+            Span::styled("  | ", Style::default().bold()),
+            Span::styled("Mod: ", Style::default().bold()),
+            Span::styled(
+                mod_wheel_target,
+                Style::default().fg(Color::Rgb(186, 225, 255)).bold(),
+            ),
         ]));
 
         Paragraph::new(text).render(param_line_a[0], buf);
 
-        Fader::new("   den", self.draw_data.state.density / 50.0).render(param_line_b[0], buf);
-        Fader::new("   len", self.draw_data.state.grain_length).render(param_line_c[0], buf);
+        Fader::new("   den", self.draw_data.modulated.density / 50.0).render(param_line_b[0], buf);
+        Fader::new("   len", self.draw_data.modulated.grain_length).render(param_line_c[0], buf);
 
         Fader::new("   spd", self.draw_data.state.play_speed / 2.0).render(param_line_b[1], buf);
-        Fader::new("   spy", self.draw_data.state.spray).render(param_line_c[1], buf);
-
-        if self.draw_data.state.pan > 0.0 {
-            Span::from(format!("  pan:  R{:.2}  ", self.draw_data.state.pan.abs()))
-                .render(param_line_b[2], buf);
-        } else if self.draw_data.state.pan < 0.0 {
-            Span::from(format!("  pan:  L{:.2}  ", self.draw_data.state.pan.abs()))
-                .render(param_line_b[2], buf);
+        Fader::new("   spy", self.draw_data.modulated.spray).render(param_line_c[1], buf);
+
+        if self.draw_data.modulated.pan > 0.0 {
+            Span::from(format!(
+                "  pan:  R{:.2}  ",
+                self.draw_data.modulated.pan.abs()
+            ))
+            .render(param_line_b[2], buf);
+        } else if self.draw_data.modulated.pan < 0.0 {
+            Span::from(format!(
+                "  pan:  L{:.2}  ",
+                self.draw_data.modulated.pan.abs()
+            ))
+            .render(param_line_b[2], buf);
         } else {
             Span::from(format!("  pan:   -C-   ")).render(param_line_b[2], buf);
         }
 
-        Fader::new("  spr", self.draw_data.state.spread).render(param_line_c[2], buf);
+        Fader::new("  spr", self.draw_data.modulated.spread).render(param_line_c[2], buf);
 
         Fader::new("  att", self.draw_data.state.attack / 5.0).render(param_line_b[3], buf);
         Fader::new("  rel", self.draw_data.state.release / 5.0).render(param_line_c[3], buf);
 
         Span::from(format!("  pch: {} ", self.draw_data.state.pitch)).render(param_line_b[4], buf);
-        Fader::new("  vol", self.draw_data.state.gain).render(param_line_c[4], buf);
+        Fader::new("  vol", self.draw_data.modulated.gain).render(param_line_c[4], buf);
     }
 }